@@ -0,0 +1,345 @@
+/*
+ * libeci: Ethereum WebAssembly ABI compliance library
+ *
+ * Copyright (c) 2018 Jake Lang
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+use checker::{check_with, run_checks, Report};
+use checklist::{CheckResult, EciChecklist};
+use parity_wasm::elements::Module;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Runs a checklist against many modules concurrently on a pool of worker threads.
+#[derive(Clone, Debug)]
+pub struct BatchChecker {
+    threads: usize,
+    parallel_checks: bool,
+}
+
+/// Reports for every module in a batch, along with throughput statistics.
+#[derive(Clone, Debug)]
+pub struct BatchReport {
+    reports: Vec<Report>,
+    elapsed: Duration,
+    check_times: HashMap<String, Duration>,
+}
+
+impl BatchChecker {
+    /// Constructs a batch checker using one worker thread per available CPU.
+    pub fn new() -> Self {
+        BatchChecker {
+            threads: thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(1),
+            parallel_checks: false,
+        }
+    }
+
+    /// Sets the number of worker threads. Zero is treated as one.
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// Sets whether the independent checks of a single module are also spread across threads. The
+    /// threads are divided between the modules being checked at once, so this only helps when
+    /// there are fewer modules than threads.
+    pub fn parallel_checks(mut self, enabled: bool) -> Self {
+        self.parallel_checks = enabled;
+        self
+    }
+
+    /// Checks every module against the checklist. Reports are returned in input order.
+    pub fn run<M: AsRef<[u8]> + Sync>(
+        &self,
+        modules: &[M],
+        checklist: &EciChecklist,
+    ) -> BatchReport {
+        let start = Instant::now();
+        let next = AtomicUsize::new(0);
+        let results: Mutex<Vec<Option<Report>>> = Mutex::new(vec![None; modules.len()]);
+        let check_times: Mutex<HashMap<String, Duration>> = Mutex::new(HashMap::new());
+        let workers = self.threads.min(modules.len());
+        let check_threads = self.check_threads(workers);
+
+        thread::scope(|scope| {
+            for _ in 0..workers {
+                scope.spawn(|| {
+                    let mut times: HashMap<String, Duration> = HashMap::new();
+
+                    loop {
+                        let idx = next.fetch_add(1, Ordering::SeqCst);
+                        if idx >= modules.len() {
+                            break;
+                        }
+
+                        let code = modules[idx].as_ref();
+                        let report = check_with(code, checklist, |module| {
+                            check_module(module, code, checklist, check_threads)
+                        });
+                        for (id, elapsed) in report.timings() {
+                            *times.entry(id.clone()).or_default() += *elapsed;
+                        }
+                        results.lock().unwrap()[idx] = Some(report);
+                    }
+
+                    let mut totals = check_times.lock().unwrap();
+                    for (id, elapsed) in times {
+                        *totals.entry(id).or_default() += elapsed;
+                    }
+                });
+            }
+        });
+
+        BatchReport {
+            reports: results
+                .into_inner()
+                .unwrap()
+                .into_iter()
+                .map(|report| report.expect("every module is checked"))
+                .collect(),
+            elapsed: start.elapsed(),
+            check_times: check_times.into_inner().unwrap(),
+        }
+    }
+
+    /// Returns how many threads each of the given number of file workers may spread its checks
+    /// across, so that no more than the configured number of threads run at once.
+    fn check_threads(&self, workers: usize) -> usize {
+        if self.parallel_checks {
+            self.threads / workers.max(1)
+        } else {
+            1
+        }
+    }
+}
+
+/// Runs the checklist against one decoded module, splitting the checks across the given number
+/// of threads.
+fn check_module(
+    module: &Module,
+    code: &[u8],
+    checklist: &EciChecklist,
+    threads: usize,
+) -> Vec<(String, CheckResult, Duration)> {
+    let ids: Vec<&str> = checklist.checklist.keys().map(|id| id.as_str()).collect();
+
+    if threads < 2 || ids.len() < 2 {
+        return run_checks(module, code, checklist, &ids);
+    }
+
+    let chunk_size = ids.len().div_ceil(threads);
+    thread::scope(|scope| {
+        let handles: Vec<_> = ids
+            .chunks(chunk_size)
+            .map(|chunk| scope.spawn(move || run_checks(module, code, checklist, chunk)))
+            .collect();
+
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap())
+            .collect()
+    })
+}
+
+impl Default for BatchChecker {
+    fn default() -> Self {
+        BatchChecker::new()
+    }
+}
+
+impl BatchReport {
+    /// Returns the report of each module, in the order the modules were given.
+    pub fn reports(&self) -> &[Report] {
+        &self.reports
+    }
+
+    /// Returns the wall-clock time taken by the whole batch.
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// Returns the number of modules checked per second of wall-clock time.
+    pub fn modules_per_second(&self) -> f64 {
+        let secs = self.elapsed.as_secs_f64();
+        if secs > 0.0 {
+            self.reports.len() as f64 / secs
+        } else {
+            0.0
+        }
+    }
+
+    /// Returns the cumulative time spent in each check across every module.
+    pub fn check_times(&self) -> &HashMap<String, Duration> {
+        &self.check_times
+    }
+
+    /// Returns the number of modules for which every check is good.
+    pub fn good_count(&self) -> usize {
+        self.reports
+            .iter()
+            .filter(|report| report.is_good())
+            .count()
+    }
+
+    /// Writes the throughput statistics and cumulative check times to stdout.
+    pub fn print_summary(&self) {
+        println!(
+            "MODULES: {} - GOOD: {} - ELAPSED: {:?} - MODULES/S: {:.2}",
+            self.reports.len(),
+            self.good_count(),
+            self.elapsed,
+            self.modules_per_second()
+        );
+
+        let mut times: Vec<(&String, &Duration)> = self.check_times.iter().collect();
+        times.sort_by(|a, b| b.1.cmp(a.1));
+        for (id, elapsed) in times {
+            println!("ID: {} - TIME: {:?}", id, elapsed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use batch::BatchChecker;
+    use checker::ModuleError;
+    use checklist::{CheckContext, CheckResult, CheckStatus, EciChecklist};
+
+    fn modules() -> Vec<Vec<u8>> {
+        vec![
+            // Exports "main" but not "memory".
+            vec![
+                0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x04, 0x01, 0x60, 0x00, 0x00,
+                0x03, 0x02, 0x01, 0x00, 0x07, 0x08, 0x01, 0x04, 0x6d, 0x61, 0x69, 0x6e, 0x00, 0x00,
+                0x0a, 0x04, 0x01, 0x02, 0x00, 0x0b,
+            ],
+            // Not a WASM module.
+            vec![0x00, 0x77, 0x61, 0x73, 0x6d],
+            // Exports "memory" but not "main".
+            vec![
+                0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x05, 0x03, 0x01, 0x00, 0x00, 0x07,
+                0x0a, 0x01, 0x06, 0x6d, 0x65, 0x6d, 0x6f, 0x72, 0x79, 0x02, 0x00,
+            ],
+        ]
+    }
+
+    #[test]
+    fn batch_reports_in_order() {
+        let modules = modules();
+        let batch = BatchChecker::new()
+            .threads(2)
+            .run(&modules, &EciChecklist::default());
+
+        assert_eq!(batch.reports().len(), 3);
+        assert_eq!(
+            batch.reports()[0].status("export-main"),
+            Some(CheckStatus::Good)
+        );
+        assert!(batch.reports()[1].error().is_some());
+        assert_eq!(
+            batch.reports()[2].status("export-memory"),
+            Some(CheckStatus::Good)
+        );
+        assert_eq!(batch.good_count(), 0);
+    }
+
+    #[test]
+    fn batch_parallel_checks_match_serial() {
+        let modules = modules();
+        let checklist = EciChecklist::default();
+        let serial = BatchChecker::new().threads(1).run(&modules, &checklist);
+        let parallel = BatchChecker::new()
+            .threads(4)
+            .parallel_checks(true)
+            .run(&modules, &checklist);
+
+        for (a, b) in serial.reports().iter().zip(parallel.reports()) {
+            let mut a = a.dump_checks();
+            let mut b = b.dump_checks();
+            a.sort_by(|x, y| x.0.cmp(&y.0));
+            b.sort_by(|x, y| x.0.cmp(&y.0));
+            assert_eq!(a, b);
+        }
+    }
+
+    #[test]
+    fn parallel_checks_share_the_threads() {
+        let batch = BatchChecker::new().threads(8).parallel_checks(true);
+        assert_eq!(batch.check_threads(2), 4);
+        assert_eq!(batch.check_threads(8), 1);
+        assert_eq!(BatchChecker::new().threads(8).check_threads(1), 1);
+    }
+
+    fn panics(_ctx: &CheckContext) -> CheckResult {
+        panic!("check failed to handle the module")
+    }
+
+    #[test]
+    fn panicking_check_fails_only_its_reports() {
+        let modules = modules();
+        let mut checklist = EciChecklist::default();
+        checklist.add_detailed_check("panics", panics);
+        let batch = BatchChecker::new()
+            .threads(2)
+            .parallel_checks(true)
+            .run(&modules, &checklist);
+
+        assert_eq!(batch.reports().len(), 3);
+        assert_eq!(
+            batch.reports()[0].error(),
+            Some(&ModuleError::Panicked(
+                "check failed to handle the module".to_string()
+            ))
+        );
+        assert!(matches!(
+            batch.reports()[1].error(),
+            Some(&ModuleError::Structure(_))
+        ));
+        assert!(matches!(
+            batch.reports()[2].error(),
+            Some(&ModuleError::Panicked(_))
+        ));
+    }
+
+    #[test]
+    fn batch_check_times() {
+        let modules = modules();
+        let checklist = EciChecklist::default();
+        let batch = BatchChecker::new().run(&modules, &checklist);
+
+        assert_eq!(batch.check_times().len(), checklist.checklist.len());
+        assert!(batch.check_times().contains_key("eei-funcsigs"));
+        assert!(batch.modules_per_second() >= 0.0);
+    }
+
+    #[test]
+    fn empty_batch() {
+        let modules: Vec<Vec<u8>> = Vec::new();
+        let batch = BatchChecker::new().run(&modules, &EciChecklist::default());
+        assert!(batch.reports().is_empty());
+        assert!(batch.check_times().is_empty());
+    }
+}
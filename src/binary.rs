@@ -0,0 +1,175 @@
+/*
+ * libeci: Ethereum WebAssembly ABI compliance library
+ *
+ * Copyright (c) 2018 Jake Lang
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+/// Errors encountered while reading a LEB128 integer.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum LebError {
+    /// The input ends in the middle of the integer.
+    UnexpectedEnd,
+    /// The integer is longer than its type allows, or sets bits beyond its width.
+    Overflow,
+}
+
+/// Reads a LEB128 integer of the given width, returning its value and encoded length in bytes.
+/// Bits of the final byte beyond the width must be zero, or copies of the sign bit if the
+/// integer is signed.
+pub fn read_leb(
+    code: &[u8],
+    offset: usize,
+    signed: bool,
+    bits: u32,
+) -> Result<(i128, usize), LebError> {
+    let max_len = bits.div_ceil(7) as usize;
+    let mut value: i128 = 0;
+    let mut shift: u32 = 0;
+
+    for i in 0..max_len {
+        let byte = *code
+            .get(offset.saturating_add(i))
+            .ok_or(LebError::UnexpectedEnd)?;
+        if i + 1 == max_len && !fits_final_byte(byte, signed, bits - shift) {
+            return Err(LebError::Overflow);
+        }
+        value |= i128::from(byte & 0x7f) << shift;
+        shift += 7;
+
+        if byte & 0x80 == 0 {
+            if signed && byte & 0x40 != 0 {
+                value |= -1i128 << shift;
+            }
+            return Ok((value, i + 1));
+        }
+    }
+    Err(LebError::Overflow)
+}
+
+/// Whether the last byte a LEB128 integer may have only uses its remaining `used` bits, the
+/// rest being zero or, for a signed integer, copies of the sign bit.
+fn fits_final_byte(byte: u8, signed: bool, used: u32) -> bool {
+    if byte & 0x80 != 0 {
+        return true;
+    }
+    let value_bits = if signed { used - 1 } else { used };
+    let unused = 0x7f & !((1u8 << value_bits) - 1);
+    byte & unused == 0 || (signed && byte & unused == unused)
+}
+
+/// An immediate operand of an instruction, as encoded in the binary format.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Immediate {
+    /// A single byte: a block type, or a reserved table or memory index.
+    Byte,
+    /// An unsigned 32-bit LEB128 integer, described by what it holds.
+    VarUint32(&'static str),
+    /// A signed 32-bit LEB128 integer.
+    VarInt32,
+    /// A signed 64-bit LEB128 integer.
+    VarInt64,
+    /// A fixed number of raw bytes.
+    Bytes(usize),
+    /// The target depths of a branch table, preceded by their number. The default depth
+    /// follows as a separate immediate.
+    BranchTargets,
+}
+
+/// Returns the immediates which follow an opcode, or None if the opcode is unknown.
+pub fn immediates(opcode: u8) -> Option<&'static [Immediate]> {
+    use self::Immediate::*;
+
+    Some(match opcode {
+        // block, loop, if
+        0x02..=0x04 => &[Byte],
+        // br, br_if
+        0x0c | 0x0d => &[VarUint32("branch depth")],
+        // br_table
+        0x0e => &[BranchTargets, VarUint32("branch depth")],
+        // call
+        0x10 => &[VarUint32("function index")],
+        // call_indirect
+        0x11 => &[VarUint32("type index"), Byte],
+        // get_local, set_local, tee_local
+        0x20..=0x22 => &[VarUint32("local index")],
+        // get_global, set_global
+        0x23 | 0x24 => &[VarUint32("global index")],
+        // loads and stores
+        0x28..=0x3e => &[VarUint32("memory alignment"), VarUint32("memory offset")],
+        // current_memory, grow_memory
+        0x3f | 0x40 => &[Byte],
+        0x41 => &[VarInt32],
+        0x42 => &[VarInt64],
+        0x43 => &[Bytes(4)],
+        0x44 => &[Bytes(8)],
+        0x00 | 0x01 | 0x05 | 0x0b | 0x0f | 0x1a | 0x1b | 0x45..=0xbf => &[],
+        _ => return None,
+    })
+}
+
+/// Whether an opcode opens a structured control instruction: block, loop or if.
+pub fn opens_block(opcode: u8) -> bool {
+    (0x02..=0x04).contains(&opcode)
+}
+
+/// The opcode which closes a structured control instruction or a function body.
+pub const END: u8 = 0x0b;
+
+#[cfg(test)]
+mod tests {
+    use binary::*;
+
+    #[test]
+    fn leb() {
+        assert_eq!(read_leb(&[0xe5, 0x8e, 0x26], 0, false, 32), Ok((624485, 3)));
+        assert_eq!(read_leb(&[0xc0, 0xbb, 0x78], 0, true, 32), Ok((-123456, 3)));
+        assert_eq!(
+            read_leb(&[0x80, 0x80], 0, false, 32),
+            Err(LebError::UnexpectedEnd)
+        );
+        assert_eq!(
+            read_leb(&[0x80, 0x80, 0x80, 0x80, 0x80, 0x00], 0, false, 32),
+            Err(LebError::Overflow)
+        );
+        // Bits above 32 are set, or not copies of the sign bit.
+        assert_eq!(
+            read_leb(&[0x80, 0x80, 0x80, 0x80, 0x10], 0, false, 32),
+            Err(LebError::Overflow)
+        );
+        assert_eq!(
+            read_leb(&[0xff, 0xff, 0xff, 0xff, 0x7f], 0, true, 32),
+            Ok((-1, 5))
+        );
+        assert_eq!(
+            read_leb(&[0xff, 0xff, 0xff, 0xff, 0x4f], 0, true, 32),
+            Err(LebError::Overflow)
+        );
+    }
+
+    #[test]
+    fn opcode_immediates() {
+        assert_eq!(immediates(0x01), Some(&[][..]));
+        assert_eq!(immediates(0x11).unwrap().len(), 2);
+        assert_eq!(immediates(0x42), Some(&[Immediate::VarInt64][..]));
+        assert!(immediates(0xff).is_none());
+        assert!(opens_block(0x03) && !opens_block(END));
+    }
+}
@@ -0,0 +1,356 @@
+/*
+ * libeci: Ethereum WebAssembly ABI compliance library
+ *
+ * Copyright (c) 2018 Jake Lang
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+use eei::{HostFunction, ImportInterfaceMap};
+use parity_wasm::elements::ValueType;
+use pwasm::value_type_name;
+
+const RUST_KEYWORDS: &[&str] = &[
+    "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "crate",
+    "do", "dyn", "else", "enum", "extern", "false", "final", "fn", "for", "if", "impl", "in",
+    "let", "loop", "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref",
+    "return", "self", "static", "struct", "super", "trait", "true", "try", "type", "typeof",
+    "unsafe", "unsized", "use", "virtual", "where", "while", "yield",
+];
+
+const C_KEYWORDS: &[&str] = &[
+    "auto", "break", "case", "char", "const", "continue", "default", "do", "double", "else",
+    "enum", "extern", "float", "for", "goto", "if", "inline", "int", "long", "register",
+    "restrict", "return", "short", "signed", "sizeof", "static", "struct", "switch", "typedef",
+    "union", "unsigned", "void", "volatile", "while",
+];
+
+const ASSEMBLYSCRIPT_KEYWORDS: &[&str] = &[
+    "break",
+    "case",
+    "catch",
+    "class",
+    "const",
+    "continue",
+    "debugger",
+    "default",
+    "delete",
+    "do",
+    "else",
+    "enum",
+    "export",
+    "extends",
+    "false",
+    "finally",
+    "for",
+    "function",
+    "if",
+    "implements",
+    "import",
+    "in",
+    "instanceof",
+    "interface",
+    "let",
+    "new",
+    "null",
+    "package",
+    "private",
+    "protected",
+    "public",
+    "return",
+    "static",
+    "super",
+    "switch",
+    "this",
+    "throw",
+    "true",
+    "try",
+    "typeof",
+    "var",
+    "void",
+    "while",
+    "with",
+    "yield",
+];
+
+/// Returns the name of a host function's parameter, or a placeholder if the interface does not
+/// name it.
+fn param_name(func: &HostFunction, index: usize) -> String {
+    match func.param_name(index) {
+        Some(name) => name.to_string(),
+        None => format!("arg{}", index),
+    }
+}
+
+/// Appends an underscore to names which are reserved words of the target language.
+fn escape(name: &str, keywords: &[&str]) -> String {
+    if keywords.contains(&name) {
+        format!("{}_", name)
+    } else {
+        name.to_string()
+    }
+}
+
+/// Returns the namespaces of the map's functions, in alphabetical order.
+fn namespaces(eei: &ImportInterfaceMap) -> Vec<&str> {
+    let mut namespaces: Vec<&str> = eei.functions().iter().map(|f| f.namespace()).collect();
+    namespaces.dedup();
+    namespaces
+}
+
+/// Generates Rust bindings for the host functions of an interface map, with one module and
+/// `extern "C"` block per namespace.
+pub fn rust_bindings(eei: &ImportInterfaceMap) -> String {
+    let mut out = format!(
+        "// Generated by libeci from EEI revision {}.\n",
+        eei.revision_number()
+    );
+
+    for namespace in namespaces(eei) {
+        out.push_str(&format!(
+            "\npub mod {} {{\n    #[link(wasm_import_module = \"{}\")]\n    #[allow(non_snake_case)]\n    extern \"C\" {{\n",
+            escape(namespace, RUST_KEYWORDS),
+            namespace
+        ));
+
+        for func in eei
+            .functions()
+            .iter()
+            .filter(|f| f.namespace() == namespace)
+        {
+            let sig = func.signature();
+            let params: Vec<String> = sig
+                .params()
+                .iter()
+                .enumerate()
+                .map(|(i, ty)| {
+                    format!(
+                        "{}: {}",
+                        escape(&param_name(func, i), RUST_KEYWORDS),
+                        value_type_name(*ty)
+                    )
+                })
+                .collect();
+            let result = match sig.return_type() {
+                Some(ty) => format!(" -> {}", value_type_name(ty)),
+                None => String::new(),
+            };
+
+            if let Some(deprecation) = func.deprecation() {
+                out.push_str(&format!(
+                    "        #[deprecated(since = \"EEI revision {}\", note = \"{}\")]\n",
+                    deprecation.since,
+                    deprecation_note(func)
+                ));
+            }
+            let name = escape(func.name(), RUST_KEYWORDS);
+            if name != func.name() {
+                out.push_str(&format!("        #[link_name = \"{}\"]\n", func.name()));
+            }
+            out.push_str(&format!(
+                "        pub fn {}({}){};\n",
+                name,
+                params.join(", "),
+                result
+            ));
+        }
+
+        out.push_str("    }\n}\n");
+    }
+    out
+}
+
+/// Returns the C type of a value type.
+fn c_type(ty: ValueType) -> &'static str {
+    match ty {
+        ValueType::I32 => "int32_t",
+        ValueType::I64 => "int64_t",
+        ValueType::F32 => "float",
+        ValueType::F64 => "double",
+    }
+}
+
+/// Generates a C header declaring the host functions of an interface map for clang's WebAssembly
+/// target. Functions are prefixed with their namespace, e.g. `ethereum_useGas`.
+pub fn c_header(eei: &ImportInterfaceMap) -> String {
+    let guard = format!("EEI_REVISION_{}_H", eei.revision_number());
+    let mut out = format!(
+        "/* Generated by libeci from EEI revision {}. */\n\n#ifndef {}\n#define {}\n\n#include <stdint.h>\n",
+        eei.revision_number(),
+        guard,
+        guard
+    );
+
+    for func in eei.functions() {
+        let sig = func.signature();
+        let params: Vec<String> = sig
+            .params()
+            .iter()
+            .enumerate()
+            .map(|(i, ty)| {
+                format!(
+                    "{} {}",
+                    c_type(*ty),
+                    escape(&param_name(func, i), C_KEYWORDS)
+                )
+            })
+            .collect();
+
+        out.push('\n');
+        if func.deprecation().is_some() {
+            out.push_str(&format!("/* Deprecated: {} */\n", deprecation_note(func)));
+        }
+        out.push_str(&format!(
+            "__attribute__((import_module(\"{}\"), import_name(\"{}\")))\n{} {}_{}({});\n",
+            func.namespace(),
+            func.name(),
+            sig.return_type().map(c_type).unwrap_or("void"),
+            func.namespace(),
+            func.name(),
+            if params.is_empty() {
+                "void".to_string()
+            } else {
+                params.join(", ")
+            }
+        ));
+    }
+
+    out.push_str(&format!("\n#endif /* {} */\n", guard));
+    out
+}
+
+/// Generates AssemblyScript declarations for the host functions of an interface map.
+pub fn assemblyscript_bindings(eei: &ImportInterfaceMap) -> String {
+    let mut out = format!(
+        "// Generated by libeci from EEI revision {}.\n",
+        eei.revision_number()
+    );
+
+    for func in eei.functions() {
+        let sig = func.signature();
+        let params: Vec<String> = sig
+            .params()
+            .iter()
+            .enumerate()
+            .map(|(i, ty)| {
+                format!(
+                    "{}: {}",
+                    escape(&param_name(func, i), ASSEMBLYSCRIPT_KEYWORDS),
+                    value_type_name(*ty)
+                )
+            })
+            .collect();
+
+        out.push('\n');
+        if func.deprecation().is_some() {
+            out.push_str(&format!("/** @deprecated {} */\n", deprecation_note(func)));
+        }
+        out.push_str(&format!(
+            "@external(\"{}\", \"{}\")\nexport declare function {}({}): {};\n",
+            func.namespace(),
+            func.name(),
+            escape(func.name(), ASSEMBLYSCRIPT_KEYWORDS),
+            params.join(", "),
+            sig.return_type().map(value_type_name).unwrap_or("void")
+        ));
+    }
+    out
+}
+
+/// Describes a deprecated function's replacement and the reason for its deprecation.
+fn deprecation_note(func: &HostFunction) -> String {
+    let deprecation = match func.deprecation() {
+        Some(deprecation) => deprecation,
+        None => return String::new(),
+    };
+
+    let mut note = format!("deprecated since EEI revision {}", deprecation.since);
+    if let Some(ref replacement) = deprecation.replacement {
+        note.push_str(&format!(", use {} instead", replacement));
+    }
+    if let Some(ref reason) = deprecation.note {
+        note.push_str(&format!(" ({})", reason));
+    }
+    note.replace('"', "'").replace("*/", "* /")
+}
+
+#[cfg(test)]
+mod tests {
+    use bindings::*;
+
+    #[test]
+    fn rust() {
+        let rust = rust_bindings(&ImportInterfaceMap::revision(3));
+        assert!(rust.starts_with("// Generated by libeci from EEI revision 3.\n"));
+        assert!(
+            rust.contains("pub mod ethereum {\n    #[link(wasm_import_module = \"ethereum\")]\n")
+        );
+        assert!(rust.contains("        pub fn useGas(amount: i64);\n"));
+        assert!(rust.contains("        pub fn getGasLeft() -> i64;\n"));
+        assert!(rust.contains("pub mod debug {"));
+
+        let eei = ImportInterfaceMap::from_spec(
+            "revision 1\nnamespace ethereum\nreturn(dataOffset: i32, dataLength: i32)",
+        )
+        .unwrap();
+        assert!(rust_bindings(&eei).contains(
+            "        #[link_name = \"return\"]\n        pub fn return_(dataOffset: i32, dataLength: i32);\n"
+        ));
+    }
+
+    #[test]
+    fn rust_deprecation() {
+        let eei = ImportInterfaceMap::from_spec(
+            "revision 1\nnamespace env\nfoo()\nbar()\nrevision 2\nnamespace env\ndeprecate foo by bar: bar is faster",
+        )
+        .unwrap();
+        assert!(rust_bindings(&eei).contains(
+            "        #[deprecated(since = \"EEI revision 2\", note = \"deprecated since EEI revision 2, use bar instead (bar is faster)\")]\n        pub fn foo("
+        ));
+    }
+
+    #[test]
+    fn c() {
+        let header = c_header(&ImportInterfaceMap::revision(3));
+        assert!(header.contains("#ifndef EEI_REVISION_3_H\n"));
+        assert!(header.contains(
+            "__attribute__((import_module(\"ethereum\"), import_name(\"getBlockHash\")))\nint32_t ethereum_getBlockHash(int64_t number, int32_t resultOffset);\n"
+        ));
+        assert!(header.contains("int64_t ethereum_getGasLeft(void);\n"));
+        assert!(header.contains("void debug_print32(int32_t value);\n"));
+        assert!(header.ends_with("#endif /* EEI_REVISION_3_H */\n"));
+    }
+
+    #[test]
+    fn assemblyscript() {
+        let decls = assemblyscript_bindings(&ImportInterfaceMap::revision(3));
+        assert!(decls.contains(
+            "@external(\"ethereum\", \"finish\")\nexport declare function finish(dataOffset: i32, dataLength: i32): void;\n"
+        ));
+        assert!(decls.contains("export declare function getGasLeft(): i64;\n"));
+    }
+
+    #[test]
+    fn unnamed_parameters() {
+        let eei = ImportInterfaceMap::from_spec("revision 1\nnamespace env\nfoo(i32, i64) -> i32")
+            .unwrap();
+        assert!(rust_bindings(&eei).contains("pub fn foo(arg0: i32, arg1: i64) -> i32;"));
+        assert!(c_header(&eei).contains("int32_t env_foo(int32_t arg0, int64_t arg1);"));
+    }
+}
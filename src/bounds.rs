@@ -0,0 +1,282 @@
+/*
+ * libeci: Ethereum WebAssembly ABI compliance library
+ *
+ * Copyright (c) 2018 Jake Lang
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+use eei::{HostFunction, ImportInterfaceMap, ParamSemantics};
+use parity_wasm::elements::{External, Instruction, Module};
+
+/// The size of a WebAssembly memory page in bytes.
+pub const PAGE_SIZE: u64 = 65536;
+
+/// A range of memory a host function call accesses, where both the pointer and the length are
+/// compile-time constants.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MemoryAccess {
+    /// The namespace and name of the called host function, e.g. "ethereum.getCaller".
+    pub callee: String,
+    /// The callable index of the calling function.
+    pub function: u32,
+    /// The index of the call instruction within the calling function's body.
+    pub instruction: usize,
+    /// The index of the pointer parameter.
+    pub param: usize,
+    pub write: bool,
+    pub offset: u64,
+    pub length: u64,
+}
+
+impl MemoryAccess {
+    /// Returns the offset one past the last byte accessed.
+    pub fn end(&self) -> u64 {
+        self.offset.saturating_add(self.length)
+    }
+}
+
+/// Returns the initial size of the module's memory in bytes, whether defined or imported, if it
+/// has one.
+pub fn initial_memory_size(module: &Module) -> Option<u64> {
+    let imported = module.import_section().and_then(|imports| {
+        imports
+            .entries()
+            .iter()
+            .find_map(|entry| match *entry.external() {
+                External::Memory(ref memory) => Some(memory.limits().initial()),
+                _ => None,
+            })
+    });
+    let defined = module
+        .memory_section()
+        .and_then(|memory| memory.entries().first())
+        .map(|memory| memory.limits().initial());
+
+    imported.or(defined).map(|pages| pages as u64 * PAGE_SIZE)
+}
+
+/// Determines whether any function body grows memory at runtime.
+pub fn grows_memory(module: &Module) -> bool {
+    module.code_section().is_some_and(|code| {
+        code.bodies().iter().any(|body| {
+            body.code()
+                .elements()
+                .iter()
+                .any(|instr| matches!(*instr, Instruction::GrowMemory(_)))
+        })
+    })
+}
+
+/// Finds every host function call whose pointer and length arguments, as described by the
+/// interface's parameter semantics, are constants. An argument is only known when it is pushed
+/// by a constant in the run of constant, local and global reads directly preceding the call. The
+/// topics of `log` beyond its number of topics are not read, and if that number is not a constant,
+/// none of them are known to be read.
+pub fn constant_accesses(module: &Module, eei: &ImportInterfaceMap) -> Vec<MemoryAccess> {
+    let imports: Vec<(&str, &str)> = match module.import_section() {
+        Some(imports) => imports
+            .entries()
+            .iter()
+            .filter(|entry| matches!(*entry.external(), External::Function(_)))
+            .map(|entry| (entry.module(), entry.field()))
+            .collect(),
+        None => return Vec::new(),
+    };
+    let bodies = match module.code_section() {
+        Some(code) => code.bodies(),
+        None => return Vec::new(),
+    };
+
+    let mut accesses: Vec<MemoryAccess> = Vec::new();
+    for (body_index, body) in bodies.iter().enumerate() {
+        let function = (imports.len() + body_index) as u32;
+        let code = body.code().elements();
+
+        for (instr_index, instr) in code.iter().enumerate() {
+            let (namespace, field) = match *instr {
+                Instruction::Call(idx) if (idx as usize) < imports.len() => imports[idx as usize],
+                _ => continue,
+            };
+            let host = match eei.get_host_function(namespace, field) {
+                Some(host) => host,
+                None => continue,
+            };
+
+            let args = constant_args(&code[..instr_index], host.signature().params().len());
+            for (param, arg) in args.iter().enumerate() {
+                if !is_read(host, &args, param) {
+                    continue;
+                }
+                let (write, length) = match host.param_semantics(param) {
+                    ParamSemantics::InPtr(len) => (false, Some(len as u64)),
+                    ParamSemantics::OutPtr(len) => (true, Some(len as u64)),
+                    ParamSemantics::InPtrLen(len) => (false, args.get(len).cloned().flatten()),
+                    ParamSemantics::OutPtrLen(len) => (true, args.get(len).cloned().flatten()),
+                    _ => continue,
+                };
+                if let (Some(offset), Some(length)) = (*arg, length) {
+                    accesses.push(MemoryAccess {
+                        callee: format!("{}.{}", namespace, field),
+                        function,
+                        instruction: instr_index,
+                        param,
+                        write,
+                        offset,
+                        length,
+                    });
+                }
+            }
+        }
+    }
+    accesses
+}
+
+/// Determines whether the host reads a parameter, given the constant arguments of the call. A
+/// parameter covered by a count parameter is only read if the count includes it, and is assumed
+/// unread if the count is unknown.
+fn is_read(host: &HostFunction, args: &[Option<u64>], param: usize) -> bool {
+    (0..args.len()).all(|i| match host.param_semantics(i) {
+        ParamSemantics::Count(first, last) if first <= param && param <= last => match args[i] {
+            Some(count) => ((param - first) as u64) < count,
+            None => false,
+        },
+        _ => true,
+    })
+}
+
+/// Recovers the constant arguments of a call with the given number of parameters from the
+/// instructions preceding it. Arguments whose value is unknown are None.
+fn constant_args(preceding: &[Instruction], count: usize) -> Vec<Option<u64>> {
+    let mut args: Vec<Option<u64>> = vec![None; count];
+
+    for (i, instr) in preceding.iter().rev().take(count).enumerate() {
+        args[count - 1 - i] = match *instr {
+            Instruction::I32Const(value) => Some(value as u32 as u64),
+            Instruction::I64Const(value) => Some(value as u64),
+            Instruction::GetLocal(_) | Instruction::GetGlobal(_) => None,
+            _ => break,
+        };
+    }
+    args
+}
+
+#[cfg(test)]
+mod tests {
+    use bounds::*;
+    use parity_wasm::deserialize_buffer;
+
+    // One page of memory. main calls getCaller at 65526 and 0, callDataCopy with an unknown
+    // result offset, and callDataCopy writing 1000 bytes at 65000.
+    const CONSTANT_CALLS: &[u8] = &[
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x0e, 0x03, 0x60, 0x01, 0x7f, 0x00,
+        0x60, 0x00, 0x00, 0x60, 0x03, 0x7f, 0x7f, 0x7f, 0x00, 0x02, 0x2e, 0x02, 0x08, 0x65, 0x74,
+        0x68, 0x65, 0x72, 0x65, 0x75, 0x6d, 0x09, 0x67, 0x65, 0x74, 0x43, 0x61, 0x6c, 0x6c, 0x65,
+        0x72, 0x00, 0x00, 0x08, 0x65, 0x74, 0x68, 0x65, 0x72, 0x65, 0x75, 0x6d, 0x0c, 0x63, 0x61,
+        0x6c, 0x6c, 0x44, 0x61, 0x74, 0x61, 0x43, 0x6f, 0x70, 0x79, 0x00, 0x02, 0x03, 0x02, 0x01,
+        0x01, 0x05, 0x03, 0x01, 0x00, 0x01, 0x07, 0x08, 0x01, 0x04, 0x6d, 0x61, 0x69, 0x6e, 0x00,
+        0x02, 0x0a, 0x24, 0x01, 0x22, 0x01, 0x01, 0x7f, 0x41, 0xf6, 0xff, 0x03, 0x10, 0x00, 0x41,
+        0x00, 0x10, 0x00, 0x20, 0x00, 0x41, 0x00, 0x41, 0xe8, 0x07, 0x10, 0x01, 0x41, 0xe8, 0xfb,
+        0x03, 0x41, 0x00, 0x41, 0xe8, 0x07, 0x10, 0x01, 0x0b,
+    ];
+
+    #[test]
+    fn memory_size() {
+        let module = deserialize_buffer::<Module>(CONSTANT_CALLS).unwrap();
+        assert_eq!(initial_memory_size(&module), Some(PAGE_SIZE));
+        assert!(!grows_memory(&module));
+    }
+
+    #[test]
+    fn finds_constant_accesses() {
+        let module = deserialize_buffer::<Module>(CONSTANT_CALLS).unwrap();
+        let accesses = constant_accesses(&module, &ImportInterfaceMap::default());
+        assert_eq!(accesses.len(), 3);
+
+        assert_eq!(
+            accesses[0],
+            MemoryAccess {
+                callee: "ethereum.getCaller".to_string(),
+                function: 2,
+                instruction: 1,
+                param: 0,
+                write: true,
+                offset: 65526,
+                length: 20,
+            }
+        );
+        assert_eq!(accesses[0].end(), 65546);
+        assert_eq!(accesses[1].offset, 0);
+        assert_eq!(accesses[2].callee, "ethereum.callDataCopy");
+        assert_eq!(accesses[2].instruction, 11);
+        assert_eq!((accesses[2].offset, accesses[2].length), (65000, 1000));
+    }
+
+    // One page of memory. main logs with one topic, passing an out of bounds second topic, then
+    // logs with a number of topics read from a local.
+    const LOG_TOPICS: &[u8] = &[
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x0e, 0x02, 0x60, 0x07, 0x7f, 0x7f,
+        0x7f, 0x7f, 0x7f, 0x7f, 0x7f, 0x00, 0x60, 0x00, 0x00, 0x02, 0x10, 0x01, 0x08, 0x65, 0x74,
+        0x68, 0x65, 0x72, 0x65, 0x75, 0x6d, 0x03, 0x6c, 0x6f, 0x67, 0x00, 0x00, 0x03, 0x02, 0x01,
+        0x01, 0x05, 0x03, 0x01, 0x00, 0x01, 0x07, 0x08, 0x01, 0x04, 0x6d, 0x61, 0x69, 0x6e, 0x00,
+        0x01, 0x0a, 0x32, 0x01, 0x30, 0x01, 0x01, 0x7f, 0x41, 0x00, 0x41, 0x00, 0x41, 0x01, 0x41,
+        0xfa, 0xff, 0x03, 0x41, 0xf0, 0xa2, 0x04, 0x41, 0x00, 0x41, 0x00, 0x10, 0x00, 0x41, 0x00,
+        0x41, 0x00, 0x20, 0x00, 0x41, 0xf0, 0xa2, 0x04, 0x41, 0xf0, 0xa2, 0x04, 0x41, 0xf0, 0xa2,
+        0x04, 0x41, 0xf0, 0xa2, 0x04, 0x10, 0x00, 0x0b,
+    ];
+
+    #[test]
+    fn access_end_saturates() {
+        let access = MemoryAccess {
+            callee: "ethereum.getCaller".to_string(),
+            function: 1,
+            instruction: 0,
+            param: 0,
+            write: true,
+            offset: u64::MAX - 4,
+            length: 20,
+        };
+        assert_eq!(access.end(), u64::MAX);
+    }
+
+    #[test]
+    fn log_topics() {
+        let module = deserialize_buffer::<Module>(LOG_TOPICS).unwrap();
+        let accesses = constant_accesses(&module, &ImportInterfaceMap::default());
+        let params: Vec<(usize, usize, u64)> = accesses
+            .iter()
+            .map(|access| (access.instruction, access.param, access.offset))
+            .collect();
+        assert_eq!(params, vec![(7, 0, 0), (7, 3, 65530), (15, 0, 0)]);
+        assert_eq!(accesses[1].length, 32);
+    }
+
+    #[test]
+    fn unknown_arguments() {
+        use parity_wasm::elements::Instruction::*;
+        assert_eq!(
+            constant_args(&[I32Const(1), GetLocal(0), I32Const(-1)], 2),
+            vec![None, Some(0xffff_ffff)]
+        );
+        assert_eq!(
+            constant_args(&[I32Const(1), I32Add, I32Const(2)], 3),
+            vec![None, None, Some(2)]
+        );
+    }
+}
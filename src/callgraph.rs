@@ -0,0 +1,412 @@
+/*
+ * libeci: Ethereum WebAssembly ABI compliance library
+ *
+ * Copyright (c) 2018 Jake Lang
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+use parity_wasm::elements::{External, Instruction, Internal, Module, Type};
+use pwasm::callable_func_type;
+use std::collections::BTreeSet;
+
+/// Which functions each function of a module may call, by callable index. Imports are nodes
+/// without callees. A `call_indirect` may call any table element whose signature matches the
+/// instruction's type. References to functions which do not exist are left out of the graph;
+/// calls to them are recorded separately.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CallGraph {
+    imports: Vec<(String, String)>,
+    direct: Vec<BTreeSet<u32>>,
+    indirect: Vec<BTreeSet<u32>>,
+    main: Option<u32>,
+    roots: BTreeSet<u32>,
+    dangling: Vec<(u32, u32)>,
+}
+
+impl CallGraph {
+    /// Builds the call graph of a module.
+    pub fn build(module: &Module) -> Self {
+        let imports: Vec<(String, String)> = module
+            .import_section()
+            .map_or(&[][..], |i| i.entries())
+            .iter()
+            .filter(|entry| matches!(*entry.external(), External::Function(_)))
+            .map(|entry| (entry.module().to_string(), entry.field().to_string()))
+            .collect();
+        let bodies = module.code_section().map_or(&[][..], |code| code.bodies());
+        let len = imports.len() + bodies.len();
+        let exists = |index: &u32| (*index as usize) < len;
+        let types = module.type_section().map_or(&[][..], |t| t.types());
+
+        let elements: BTreeSet<u32> = module
+            .elements_section()
+            .map_or(&[][..], |e| e.entries())
+            .iter()
+            .flat_map(|segment| segment.members().iter().cloned())
+            .filter(exists)
+            .collect();
+
+        let mut direct = vec![BTreeSet::new(); len];
+        let mut indirect = vec![BTreeSet::new(); len];
+        let mut dangling: Vec<(u32, u32)> = Vec::new();
+        for (body_index, body) in bodies.iter().enumerate() {
+            let caller = imports.len() + body_index;
+            for instr in body.code().elements() {
+                match *instr {
+                    Instruction::Call(callee) if exists(&callee) => {
+                        direct[caller].insert(callee);
+                    }
+                    Instruction::Call(callee) => dangling.push((caller as u32, callee)),
+                    Instruction::CallIndirect(type_ref, _) => {
+                        let expected = match types.get(type_ref as usize) {
+                            Some(Type::Function(func_type)) => func_type,
+                            None => continue,
+                        };
+                        indirect[caller].extend(elements.iter().filter(|&&element| {
+                            callable_func_type(module, element).as_ref() == Some(expected)
+                        }));
+                    }
+                    _ => (),
+                }
+            }
+        }
+
+        let main = module.export_section().and_then(|exports| {
+            exports
+                .entries()
+                .iter()
+                .filter(|export| export.field() == "main")
+                .find_map(|export| match *export.internal() {
+                    Internal::Function(index) => Some(index),
+                    _ => None,
+                })
+                .filter(exists)
+        });
+
+        let mut roots = elements;
+        roots.extend(module.start_section().filter(exists));
+        roots.extend(
+            module
+                .export_section()
+                .map_or(&[][..], |e| e.entries())
+                .iter()
+                .filter_map(|export| match *export.internal() {
+                    Internal::Function(index) => Some(index),
+                    _ => None,
+                })
+                .filter(exists),
+        );
+
+        CallGraph {
+            imports,
+            direct,
+            indirect,
+            main,
+            roots,
+            dangling,
+        }
+    }
+
+    /// Returns the number of functions, imported and defined.
+    pub fn len(&self) -> usize {
+        self.direct.len()
+    }
+
+    /// Determines whether the module has no functions.
+    pub fn is_empty(&self) -> bool {
+        self.direct.is_empty()
+    }
+
+    /// Determines whether a function is imported from the host.
+    pub fn is_import(&self, index: u32) -> bool {
+        (index as usize) < self.imports.len()
+    }
+
+    /// Returns the namespace and name of an imported function.
+    pub fn import_name(&self, index: u32) -> Option<(&str, &str)> {
+        self.imports
+            .get(index as usize)
+            .map(|(ns, name)| (ns.as_str(), name.as_str()))
+    }
+
+    /// Returns the callable index of the exported "main" function.
+    pub fn main(&self) -> Option<u32> {
+        self.main
+    }
+
+    /// Returns the calls to functions which do not exist, as (caller, callee) pairs.
+    pub fn dangling_calls(&self) -> &[(u32, u32)] {
+        &self.dangling
+    }
+
+    /// Returns the functions the host or the table may call: exported functions, the start
+    /// function and table elements.
+    pub fn roots(&self) -> &BTreeSet<u32> {
+        &self.roots
+    }
+
+    /// Returns every function reachable from the roots, which are therefore needed at runtime.
+    pub fn live_functions(&self) -> BTreeSet<u32> {
+        let roots: Vec<u32> = self.roots.iter().cloned().collect();
+        self.reachable_from(&roots)
+    }
+
+    /// Returns the functions a function calls directly.
+    pub fn direct_callees(&self, index: u32) -> &BTreeSet<u32> {
+        &self.direct[index as usize]
+    }
+
+    /// Returns the functions a function may call through the table.
+    pub fn indirect_callees(&self, index: u32) -> &BTreeSet<u32> {
+        &self.indirect[index as usize]
+    }
+
+    /// Returns every function a function may call, directly or through the table.
+    pub fn callees(&self, index: u32) -> BTreeSet<u32> {
+        self.direct_callees(index)
+            .union(self.indirect_callees(index))
+            .cloned()
+            .collect()
+    }
+
+    /// Returns every function which may call the given function.
+    pub fn callers(&self, index: u32) -> BTreeSet<u32> {
+        (0..self.len() as u32)
+            .filter(|&caller| {
+                self.direct[caller as usize].contains(&index)
+                    || self.indirect[caller as usize].contains(&index)
+            })
+            .collect()
+    }
+
+    /// Returns every function reachable from the given roots, including the roots themselves.
+    pub fn reachable_from(&self, roots: &[u32]) -> BTreeSet<u32> {
+        let mut reached: BTreeSet<u32> = BTreeSet::new();
+        let mut pending: Vec<u32> = roots
+            .iter()
+            .cloned()
+            .filter(|&root| (root as usize) < self.len())
+            .collect();
+        while let Some(index) = pending.pop() {
+            if reached.insert(index) {
+                pending.extend(self.callees(index));
+            }
+        }
+        reached
+    }
+
+    /// Returns every function reachable from "main", or nothing if "main" is not exported.
+    pub fn reachable_from_main(&self) -> BTreeSet<u32> {
+        match self.main {
+            Some(main) => self.reachable_from(&[main]),
+            None => BTreeSet::new(),
+        }
+    }
+
+    /// Returns the imported functions a function may transitively call.
+    pub fn reachable_host_functions(&self, index: u32) -> BTreeSet<u32> {
+        self.reachable_from(&[index])
+            .into_iter()
+            .filter(|&f| self.is_import(f))
+            .collect()
+    }
+
+    /// Returns the groups of mutually recursive functions, including functions which call
+    /// themselves, each sorted by callable index.
+    pub fn cycles(&self) -> Vec<Vec<u32>> {
+        let mut cycles: Vec<Vec<u32>> = self
+            .strongly_connected()
+            .into_iter()
+            .filter(|scc| scc.len() > 1 || self.callees(scc[0]).contains(&scc[0]))
+            .map(|mut scc| {
+                scc.sort();
+                scc
+            })
+            .collect();
+        cycles.sort();
+        cycles
+    }
+
+    /// Determines whether a function may call itself, directly or through other functions.
+    pub fn is_recursive(&self, index: u32) -> bool {
+        self.callees(index)
+            .iter()
+            .any(|&callee| self.reachable_from(&[callee]).contains(&index))
+    }
+
+    /// Partitions the graph into strongly connected components using an iterative version of
+    /// Tarjan's algorithm, so that deep call chains cannot overflow the stack.
+    fn strongly_connected(&self) -> Vec<Vec<u32>> {
+        let len = self.len();
+        let adjacency: Vec<Vec<u32>> = (0..len as u32)
+            .map(|f| {
+                self.callees(f)
+                    .into_iter()
+                    .filter(|&c| (c as usize) < len)
+                    .collect()
+            })
+            .collect();
+        let mut next_index = 0;
+        let mut indices: Vec<Option<usize>> = vec![None; len];
+        let mut lowlinks: Vec<usize> = vec![0; len];
+        let mut on_stack: Vec<bool> = vec![false; len];
+        let mut stack: Vec<u32> = Vec::new();
+        let mut components: Vec<Vec<u32>> = Vec::new();
+
+        for root in 0..len as u32 {
+            if indices[root as usize].is_some() {
+                continue;
+            }
+            let mut work: Vec<(u32, usize)> = vec![(root, 0)];
+            indices[root as usize] = Some(next_index);
+            lowlinks[root as usize] = next_index;
+            next_index += 1;
+            stack.push(root);
+            on_stack[root as usize] = true;
+
+            while let Some(&mut (node, ref mut position)) = work.last_mut() {
+                let node_index = node as usize;
+                if let Some(&callee) = adjacency[node_index].get(*position) {
+                    *position += 1;
+                    let callee_index = callee as usize;
+                    match indices[callee_index] {
+                        None => {
+                            indices[callee_index] = Some(next_index);
+                            lowlinks[callee_index] = next_index;
+                            next_index += 1;
+                            stack.push(callee);
+                            on_stack[callee_index] = true;
+                            work.push((callee, 0));
+                        }
+                        Some(index) if on_stack[callee_index] => {
+                            lowlinks[node_index] = lowlinks[node_index].min(index);
+                        }
+                        Some(_) => (),
+                    }
+                    continue;
+                }
+
+                work.pop();
+                if let Some(&(parent, _)) = work.last() {
+                    lowlinks[parent as usize] = lowlinks[parent as usize].min(lowlinks[node_index]);
+                }
+                if Some(lowlinks[node_index]) == indices[node_index] {
+                    let mut component: Vec<u32> = Vec::new();
+                    loop {
+                        let member = stack.pop().expect("component is on the stack");
+                        on_stack[member as usize] = false;
+                        component.push(member);
+                        if member == node {
+                            break;
+                        }
+                    }
+                    components.push(component);
+                }
+            }
+        }
+        components
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use callgraph::*;
+    use parity_wasm::elements::deserialize_buffer;
+
+    // Imports useGas (0) and finish (1). main (2) calls a (3) and, through the table, any
+    // (i32) -> () element; a calls useGas and c (4), which calls a back; d (5) takes an i32 and
+    // calls finish; e (6) calls itself; f (7) is in the table but has the wrong signature.
+    fn module() -> Module {
+        let code: Vec<u8> = vec![
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x11, 0x04, 0x60, 0x01, 0x7e,
+            0x00, 0x60, 0x02, 0x7f, 0x7f, 0x00, 0x60, 0x00, 0x00, 0x60, 0x01, 0x7f, 0x00, 0x02,
+            0x25, 0x02, 0x08, 0x65, 0x74, 0x68, 0x65, 0x72, 0x65, 0x75, 0x6d, 0x06, 0x75, 0x73,
+            0x65, 0x47, 0x61, 0x73, 0x00, 0x00, 0x08, 0x65, 0x74, 0x68, 0x65, 0x72, 0x65, 0x75,
+            0x6d, 0x06, 0x66, 0x69, 0x6e, 0x69, 0x73, 0x68, 0x00, 0x01, 0x03, 0x07, 0x06, 0x02,
+            0x02, 0x02, 0x03, 0x02, 0x02, 0x04, 0x04, 0x01, 0x70, 0x00, 0x02, 0x05, 0x03, 0x01,
+            0x00, 0x01, 0x07, 0x11, 0x02, 0x04, 0x6d, 0x61, 0x69, 0x6e, 0x00, 0x02, 0x06, 0x6d,
+            0x65, 0x6d, 0x6f, 0x72, 0x79, 0x02, 0x00, 0x09, 0x08, 0x01, 0x00, 0x41, 0x00, 0x0b,
+            0x02, 0x05, 0x07, 0x0a, 0x2c, 0x06, 0x0b, 0x00, 0x41, 0x00, 0x41, 0x00, 0x10, 0x03,
+            0x11, 0x03, 0x00, 0x0b, 0x08, 0x00, 0x42, 0x01, 0x10, 0x00, 0x10, 0x04, 0x0b, 0x04,
+            0x00, 0x10, 0x03, 0x0b, 0x08, 0x00, 0x41, 0x00, 0x20, 0x00, 0x10, 0x01, 0x0b, 0x04,
+            0x00, 0x10, 0x06, 0x0b, 0x02, 0x00, 0x0b,
+        ];
+        deserialize_buffer::<Module>(&code).unwrap()
+    }
+
+    #[test]
+    fn edges() {
+        let graph = CallGraph::build(&module());
+        assert_eq!(graph.len(), 8);
+        assert!(graph.is_import(1) && !graph.is_import(2));
+        assert_eq!(graph.import_name(1), Some(("ethereum", "finish")));
+        assert_eq!(graph.main(), Some(2));
+        assert_eq!(graph.callees(2), [3, 5].iter().cloned().collect());
+        assert_eq!(graph.indirect_callees(2), &[5].iter().cloned().collect());
+        assert_eq!(graph.callers(3), [2, 4].iter().cloned().collect());
+        assert!(graph.callees(0).is_empty());
+        assert_eq!(graph.roots(), &[2, 5, 7].iter().cloned().collect());
+    }
+
+    #[test]
+    fn reachability() {
+        let graph = CallGraph::build(&module());
+        assert_eq!(
+            graph.reachable_from_main(),
+            [0, 1, 2, 3, 4, 5].iter().cloned().collect()
+        );
+        assert_eq!(
+            graph.reachable_host_functions(3),
+            [0].iter().cloned().collect()
+        );
+        assert_eq!(
+            graph.reachable_host_functions(2),
+            [0, 1].iter().cloned().collect()
+        );
+        assert_eq!(
+            graph.live_functions(),
+            [0, 1, 2, 3, 4, 5, 7].iter().cloned().collect()
+        );
+    }
+
+    #[test]
+    fn recursion() {
+        let graph = CallGraph::build(&module());
+        assert_eq!(graph.cycles(), vec![vec![3, 4], vec![6]]);
+        assert!(graph.is_recursive(4));
+        assert!(graph.is_recursive(6));
+        assert!(!graph.is_recursive(2));
+    }
+
+    #[test]
+    fn dangling_call() {
+        // (func (call 5)) exported as main, where function 5 does not exist.
+        let code: Vec<u8> = vec![
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x04, 0x01, 0x60, 0x00, 0x00,
+            0x03, 0x02, 0x01, 0x00, 0x07, 0x08, 0x01, 0x04, 0x6d, 0x61, 0x69, 0x6e, 0x00, 0x00,
+            0x0a, 0x06, 0x01, 0x04, 0x00, 0x10, 0x05, 0x0b,
+        ];
+        let graph = CallGraph::build(&deserialize_buffer::<Module>(&code).unwrap());
+        assert_eq!(graph.dangling_calls(), &[(0, 5)]);
+        assert!(graph.callees(0).is_empty());
+        assert_eq!(graph.reachable_from_main(), [0].iter().cloned().collect());
+        assert!(graph.cycles().is_empty());
+    }
+}
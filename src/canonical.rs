@@ -0,0 +1,577 @@
+/*
+ * libeci: Ethereum WebAssembly ABI compliance library
+ *
+ * Copyright (c) 2018 Jake Lang
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+use binary::{immediates, read_leb, Immediate, LebError, END};
+use std::fmt;
+use std::mem;
+use structure::scan;
+
+/// A part of the bytecode which is not encoded in its canonical form.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NonCanonical {
+    /// Offset of the first byte of the offending encoding.
+    pub offset: usize,
+    /// What the encoding represents, e.g. "section size" or "function index".
+    pub what: &'static str,
+    /// Length of the encoding, in `unit`s.
+    pub length: usize,
+    /// Length of the canonical encoding, in `unit`s.
+    pub canonical_length: usize,
+    /// What the lengths count: "bytes" for integers and sizes, "declarations" for the entries of
+    /// local declarations.
+    pub unit: &'static str,
+}
+
+impl fmt::Display for NonCanonical {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "non-canonical {} at byte {}: {} {}, canonical encoding is {} {}",
+            self.what, self.offset, self.length, self.unit, self.canonical_length, self.unit
+        )
+    }
+}
+
+/// Error raised when the bytecode is too malformed to be walked.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EncodingError {
+    pub offset: usize,
+    pub message: String,
+}
+
+impl fmt::Display for EncodingError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at byte {}", self.message, self.offset)
+    }
+}
+
+/// Walks the raw bytecode and lists every encoding which is not canonical: LEB128 integers which
+/// are longer than necessary, including section and function body sizes, and local declarations
+/// which could be merged.
+pub fn find_non_canonical(code: &[u8]) -> Result<Vec<NonCanonical>, EncodingError> {
+    let mut walker = Walker::new(code);
+    walker.module()?;
+    Ok(walker.found)
+}
+
+/// Returns the canonical re-encoding of the bytecode, in which every LEB128 integer is minimal,
+/// section and body sizes are recomputed, and adjacent local declarations of the same type are
+/// merged.
+pub fn canonicalize(code: &[u8]) -> Result<Vec<u8>, EncodingError> {
+    let mut walker = Walker::new(code);
+    walker.module()?;
+    Ok(walker.out)
+}
+
+/// Reads the bytecode while writing its canonical form and recording any differences.
+struct Walker<'a> {
+    code: &'a [u8],
+    pos: usize,
+    out: Vec<u8>,
+    found: Vec<NonCanonical>,
+}
+
+impl<'a> Walker<'a> {
+    fn new(code: &'a [u8]) -> Self {
+        Walker {
+            code,
+            pos: 0,
+            out: Vec::with_capacity(code.len()),
+            found: Vec::new(),
+        }
+    }
+
+    fn error<T>(&self, message: &str) -> Result<T, EncodingError> {
+        Err(EncodingError {
+            offset: self.pos,
+            message: message.to_string(),
+        })
+    }
+
+    /// Copies a single byte.
+    fn byte(&mut self) -> Result<u8, EncodingError> {
+        match self.code.get(self.pos) {
+            Some(&byte) => {
+                self.pos += 1;
+                self.out.push(byte);
+                Ok(byte)
+            }
+            None => self.error("unexpected end of input"),
+        }
+    }
+
+    /// Copies a run of bytes.
+    fn bytes(&mut self, len: usize) -> Result<(), EncodingError> {
+        if len > self.code.len() - self.pos {
+            return self.error("unexpected end of input");
+        }
+        self.out
+            .extend_from_slice(&self.code[self.pos..self.pos + len]);
+        self.pos += len;
+        Ok(())
+    }
+
+    /// Reads a LEB128 integer of the given width without copying it.
+    fn read_leb(&mut self, signed: bool, bits: u32) -> Result<(i128, usize), EncodingError> {
+        match read_leb(self.code, self.pos, signed, bits) {
+            Ok((value, length)) => {
+                self.pos += length;
+                Ok((value, length))
+            }
+            Err(LebError::UnexpectedEnd) => {
+                self.pos = self.code.len();
+                self.error("unexpected end of input")
+            }
+            Err(LebError::Overflow) => self.error("LEB128 integer too large"),
+        }
+    }
+
+    /// Copies a LEB128 integer in its minimal form, recording it if the input was longer.
+    fn leb(&mut self, what: &'static str, signed: bool, bits: u32) -> Result<i128, EncodingError> {
+        let offset = self.pos;
+        let (value, length) = self.read_leb(signed, bits)?;
+        let canonical_length = write_leb(&mut self.out, value, signed);
+
+        if length != canonical_length {
+            self.found.push(NonCanonical {
+                offset,
+                what,
+                length,
+                canonical_length,
+                unit: "bytes",
+            });
+        }
+        Ok(value)
+    }
+
+    fn varuint32(&mut self, what: &'static str) -> Result<u32, EncodingError> {
+        self.leb(what, false, 32).map(|value| value as u32)
+    }
+
+    fn module(&mut self) -> Result<(), EncodingError> {
+        if let Err(e) = scan(self.code) {
+            return Err(EncodingError {
+                offset: e.offset,
+                message: e.to_string(),
+            });
+        }
+        self.bytes(8)?;
+
+        while self.pos < self.code.len() {
+            let id = self.byte()?;
+            let size_offset = self.pos;
+            let size = self.read_leb(false, 32)?;
+            let end = self.pos + size.0 as usize;
+
+            let outer = mem::take(&mut self.out);
+            self.section(id, end)?;
+            if self.pos != end {
+                return self.error("section contents do not match its size");
+            }
+            let payload = mem::replace(&mut self.out, outer);
+            self.sized(payload, "section size", size_offset, size);
+        }
+        Ok(())
+    }
+
+    /// Writes a size-prefixed payload whose size has been recomputed, recording the original size
+    /// prefix if it was longer than needed for its own value.
+    fn sized(&mut self, payload: Vec<u8>, what: &'static str, offset: usize, size: (i128, usize)) {
+        let (value, length) = size;
+        if length != leb_len(value, false) {
+            self.found.push(NonCanonical {
+                offset,
+                what,
+                length,
+                canonical_length: leb_len(payload.len() as i128, false),
+                unit: "bytes",
+            });
+        }
+        write_leb(&mut self.out, payload.len() as i128, false);
+        self.out.extend_from_slice(&payload);
+    }
+
+    fn section(&mut self, id: u8, end: usize) -> Result<(), EncodingError> {
+        if id == 0 {
+            let len = self.varuint32("custom section name length")?;
+            self.bytes(len as usize)?;
+            if end < self.pos {
+                return self.error("custom section name overruns the section");
+            }
+            return self.bytes(end - self.pos);
+        }
+        if id == 8 {
+            self.varuint32("start function index")?;
+            return Ok(());
+        }
+
+        let count = self.varuint32("entry count")?;
+        for _ in 0..count {
+            match id {
+                1 => self.func_type()?,
+                2 => self.import()?,
+                3 => {
+                    self.varuint32("type index")?;
+                }
+                4 => self.table_type()?,
+                5 => self.limits()?,
+                6 => {
+                    self.bytes(2)?;
+                    self.init_expr()?;
+                }
+                7 => {
+                    let len = self.varuint32("export name length")?;
+                    self.bytes(len as usize)?;
+                    self.byte()?;
+                    self.varuint32("export index")?;
+                }
+                9 => {
+                    self.varuint32("table index")?;
+                    self.init_expr()?;
+                    let members = self.varuint32("element count")?;
+                    for _ in 0..members {
+                        self.varuint32("function index")?;
+                    }
+                }
+                10 => self.func_body()?,
+                11 => {
+                    self.varuint32("memory index")?;
+                    self.init_expr()?;
+                    let len = self.varuint32("data segment length")?;
+                    self.bytes(len as usize)?;
+                }
+                _ => return self.error("unknown section"),
+            }
+        }
+        Ok(())
+    }
+
+    fn func_type(&mut self) -> Result<(), EncodingError> {
+        self.byte()?;
+        let params = self.varuint32("parameter count")?;
+        self.bytes(params as usize)?;
+        let results = self.byte()?;
+        self.bytes(results as usize)
+    }
+
+    fn import(&mut self) -> Result<(), EncodingError> {
+        let len = self.varuint32("import module name length")?;
+        self.bytes(len as usize)?;
+        let len = self.varuint32("import field name length")?;
+        self.bytes(len as usize)?;
+
+        match self.byte()? {
+            0 => {
+                self.varuint32("type index")?;
+                Ok(())
+            }
+            1 => self.table_type(),
+            2 => self.limits(),
+            3 => self.bytes(2),
+            _ => self.error("unknown import kind"),
+        }
+    }
+
+    fn table_type(&mut self) -> Result<(), EncodingError> {
+        self.byte()?;
+        self.limits()
+    }
+
+    fn limits(&mut self) -> Result<(), EncodingError> {
+        let flags = self.byte()?;
+        self.varuint32("limits initial")?;
+        if flags & 0x01 != 0 {
+            self.varuint32("limits maximum")?;
+        }
+        Ok(())
+    }
+
+    fn init_expr(&mut self) -> Result<(), EncodingError> {
+        loop {
+            if self.instruction()? == END {
+                return Ok(());
+            }
+        }
+    }
+
+    fn func_body(&mut self) -> Result<(), EncodingError> {
+        let size_offset = self.pos;
+        let size = self.read_leb(false, 32)?;
+        let end = self.pos + size.0 as usize;
+        let outer = mem::take(&mut self.out);
+
+        self.locals()?;
+        while self.pos < end {
+            self.instruction()?;
+        }
+        if self.pos != end {
+            return self.error("function body does not match its size");
+        }
+
+        let body = mem::replace(&mut self.out, outer);
+        self.sized(body, "function body size", size_offset, size);
+        Ok(())
+    }
+
+    /// Copies the local declarations of a function body, merging adjacent declarations of the
+    /// same type and dropping empty ones.
+    fn locals(&mut self) -> Result<(), EncodingError> {
+        let offset = self.pos;
+        let mut decls: Vec<(u32, u8)> = Vec::new();
+        let mut original_len: usize = 0;
+
+        // The declarations are read into a scratch buffer, then written out merged.
+        let outer = mem::take(&mut self.out);
+        let count = self.varuint32("local declaration count")?;
+        for _ in 0..count {
+            let n = self.varuint32("local count")?;
+            let value_type = self.byte()?;
+            original_len += 1;
+
+            if n == 0 {
+                continue;
+            }
+            match decls.last_mut() {
+                Some(&mut (ref mut last_n, last_type)) if last_type == value_type => {
+                    *last_n = last_n.saturating_add(n);
+                }
+                _ => decls.push((n, value_type)),
+            }
+        }
+        self.out = outer;
+
+        if decls.len() != original_len {
+            self.found.push(NonCanonical {
+                offset,
+                what: "local declarations",
+                length: original_len,
+                canonical_length: decls.len(),
+                unit: "declarations",
+            });
+        }
+        write_leb(&mut self.out, decls.len() as i128, false);
+        for (n, value_type) in decls {
+            write_leb(&mut self.out, i128::from(n), false);
+            self.out.push(value_type);
+        }
+        Ok(())
+    }
+
+    /// Copies a single instruction and its immediates, returning its opcode.
+    fn instruction(&mut self) -> Result<u8, EncodingError> {
+        let opcode = self.byte()?;
+        let immediates = match immediates(opcode) {
+            Some(immediates) => immediates,
+            None => {
+                self.pos -= 1;
+                return self.error("unknown opcode");
+            }
+        };
+
+        for immediate in immediates {
+            match *immediate {
+                Immediate::Byte => {
+                    self.byte()?;
+                }
+                Immediate::VarUint32(what) => {
+                    self.varuint32(what)?;
+                }
+                Immediate::VarInt32 => {
+                    self.leb("i32 constant", true, 32)?;
+                }
+                Immediate::VarInt64 => {
+                    self.leb("i64 constant", true, 64)?;
+                }
+                Immediate::Bytes(len) => self.bytes(len)?,
+                Immediate::BranchTargets => {
+                    let targets = self.varuint32("branch table length")?;
+                    for _ in 0..targets {
+                        self.varuint32("branch depth")?;
+                    }
+                }
+            }
+        }
+        Ok(opcode)
+    }
+}
+
+/// Returns the length of the minimal LEB128 encoding of a value.
+fn leb_len(value: i128, signed: bool) -> usize {
+    write_leb(&mut Vec::with_capacity(19), value, signed)
+}
+
+/// Writes the minimal LEB128 encoding of a value, returning its length.
+fn write_leb(out: &mut Vec<u8>, mut value: i128, signed: bool) -> usize {
+    let start = out.len();
+
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        let done = if signed {
+            (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0)
+        } else {
+            value == 0
+        };
+
+        if done {
+            out.push(byte);
+            return out.len() - start;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use canonical::*;
+
+    // A module exporting "main", whose body is "i32.const -1; drop".
+    const CANONICAL: [u8; 37] = [
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x04, 0x01, 0x60, 0x00, 0x00, 0x03,
+        0x02, 0x01, 0x00, 0x07, 0x08, 0x01, 0x04, 0x6d, 0x61, 0x69, 0x6e, 0x00, 0x00, 0x0a, 0x07,
+        0x01, 0x05, 0x00, 0x41, 0x7f, 0x1a, 0x0b,
+    ];
+
+    #[test]
+    fn canonical_module() {
+        assert!(find_non_canonical(&CANONICAL).unwrap().is_empty());
+        assert_eq!(canonicalize(&CANONICAL).unwrap(), CANONICAL.to_vec());
+    }
+
+    #[test]
+    fn overlong_section_size_and_index() {
+        let code: Vec<u8> = vec![
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x84, 0x00, 0x01, 0x60, 0x00,
+            0x00, 0x03, 0x02, 0x01, 0x00, 0x07, 0x09, 0x01, 0x04, 0x6d, 0x61, 0x69, 0x6e, 0x00,
+            0x80, 0x00, 0x0a, 0x07, 0x01, 0x05, 0x00, 0x41, 0x7f, 0x1a, 0x0b,
+        ];
+        let found = find_non_canonical(&code).unwrap();
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].what, "section size");
+        assert_eq!(found[0].offset, 9);
+        assert_eq!(found[0].length, 2);
+        assert_eq!(found[0].canonical_length, 1);
+        assert_eq!(found[1].what, "export index");
+        assert_eq!(found[1].offset, 28);
+
+        assert_eq!(canonicalize(&code).unwrap(), CANONICAL.to_vec());
+    }
+
+    #[test]
+    fn overlong_signed_constant() {
+        let code: Vec<u8> = vec![
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x04, 0x01, 0x60, 0x00, 0x00,
+            0x03, 0x02, 0x01, 0x00, 0x07, 0x08, 0x01, 0x04, 0x6d, 0x61, 0x69, 0x6e, 0x00, 0x00,
+            0x0a, 0x08, 0x01, 0x06, 0x00, 0x41, 0xff, 0x7f, 0x1a, 0x0b,
+        ];
+        let found = find_non_canonical(&code).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].what, "i32 constant");
+        assert_eq!(found[0].offset, 34);
+
+        assert_eq!(canonicalize(&code).unwrap(), CANONICAL.to_vec());
+    }
+
+    #[test]
+    fn split_locals() {
+        // Declares "local i32; local i32" as two entries rather than one.
+        let code: Vec<u8> = vec![
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x04, 0x01, 0x60, 0x00, 0x00,
+            0x03, 0x02, 0x01, 0x00, 0x0a, 0x08, 0x01, 0x06, 0x02, 0x01, 0x7f, 0x01, 0x7f, 0x0b,
+        ];
+        let found = find_non_canonical(&code).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].what, "local declarations");
+        assert_eq!(found[0].offset, 22);
+        assert_eq!(
+            found[0].to_string(),
+            "non-canonical local declarations at byte 22: 2 declarations, canonical encoding is 1 declarations"
+        );
+
+        let expected: Vec<u8> = vec![
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x04, 0x01, 0x60, 0x00, 0x00,
+            0x03, 0x02, 0x01, 0x00, 0x0a, 0x06, 0x01, 0x04, 0x01, 0x02, 0x7f, 0x0b,
+        ];
+        assert_eq!(canonicalize(&code).unwrap(), expected);
+        assert!(find_non_canonical(&expected).unwrap().is_empty());
+    }
+
+    #[test]
+    fn minimal_leb() {
+        let mut out = Vec::new();
+        assert_eq!(write_leb(&mut out, 624485, false), 3);
+        assert_eq!(out, vec![0xe5, 0x8e, 0x26]);
+        out.clear();
+        assert_eq!(write_leb(&mut out, -123456, true), 3);
+        assert_eq!(out, vec![0xc0, 0xbb, 0x78]);
+        out.clear();
+        assert_eq!(write_leb(&mut out, 64, true), 2);
+        assert_eq!(out, vec![0xc0, 0x00]);
+    }
+
+    #[test]
+    fn malformed_input() {
+        assert!(find_non_canonical(&[0x00, 0x61, 0x73]).is_err());
+    }
+
+    #[test]
+    fn overlong_local_declaration_count() {
+        // Declares no locals, with the count padded to two bytes.
+        let code: Vec<u8> = vec![
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x04, 0x01, 0x60, 0x00, 0x00,
+            0x03, 0x02, 0x01, 0x00, 0x0a, 0x05, 0x01, 0x03, 0x80, 0x00, 0x0b,
+        ];
+        let found = find_non_canonical(&code).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].what, "local declaration count");
+        assert_eq!(found[0].offset, 22);
+        assert_eq!(found[0].length, 2);
+
+        let expected: Vec<u8> = vec![
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x04, 0x01, 0x60, 0x00, 0x00,
+            0x03, 0x02, 0x01, 0x00, 0x0a, 0x04, 0x01, 0x02, 0x00, 0x0b,
+        ];
+        assert_eq!(canonicalize(&code).unwrap(), expected);
+    }
+
+    #[test]
+    fn unused_high_bits() {
+        // The export index is a five byte LEB128 with bits above 32 set.
+        let code: Vec<u8> = vec![
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x04, 0x01, 0x60, 0x00, 0x00,
+            0x03, 0x02, 0x01, 0x00, 0x07, 0x0c, 0x01, 0x04, 0x6d, 0x61, 0x69, 0x6e, 0x00, 0x80,
+            0x80, 0x80, 0x80, 0x10, 0x0a, 0x04, 0x01, 0x02, 0x00, 0x0b,
+        ];
+        let err = find_non_canonical(&code).unwrap_err();
+        assert_eq!(err.offset, 27);
+
+        // A signed 32-bit constant may only repeat the sign bit.
+        assert!(Walker::new(&[0xff, 0xff, 0xff, 0xff, 0x7f])
+            .read_leb(true, 32)
+            .is_ok());
+        assert!(Walker::new(&[0xff, 0xff, 0xff, 0xff, 0x4f])
+            .read_leb(true, 32)
+            .is_err());
+    }
+}
@@ -0,0 +1,198 @@
+/*
+ * libeci: Ethereum WebAssembly ABI compliance library
+ *
+ * Copyright (c) 2018 Jake Lang
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+use callgraph::CallGraph;
+use eei::EEI_NAMESPACE;
+use parity_wasm::elements::Module;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+/// Something a contract can do through the EEI.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Capability {
+    ReadsStorage,
+    WritesStorage,
+    Calls,
+    DelegateCalls,
+    CreatesContracts,
+    SelfDestructs,
+    EmitsLogs,
+    ReadsBlockContext,
+}
+
+impl Capability {
+    /// Returns the name of the capability as used in manifests.
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            Capability::ReadsStorage => "reads-storage",
+            Capability::WritesStorage => "writes-storage",
+            Capability::Calls => "calls",
+            Capability::DelegateCalls => "delegatecalls",
+            Capability::CreatesContracts => "creates-contracts",
+            Capability::SelfDestructs => "self-destructs",
+            Capability::EmitsLogs => "emits-logs",
+            Capability::ReadsBlockContext => "reads-block-context",
+        }
+    }
+
+    /// Returns the capability an EEI function grants, if any. `callCode` runs another contract's
+    /// code against the caller's storage, so it counts as a delegatecall.
+    pub fn of(name: &str) -> Option<Capability> {
+        match name {
+            "storageLoad" => Some(Capability::ReadsStorage),
+            "storageStore" => Some(Capability::WritesStorage),
+            "call" | "callStatic" => Some(Capability::Calls),
+            "callDelegate" | "callCode" => Some(Capability::DelegateCalls),
+            "create" => Some(Capability::CreatesContracts),
+            "selfDestruct" => Some(Capability::SelfDestructs),
+            "log" => Some(Capability::EmitsLogs),
+            "getBlockHash" | "getBlockCoinbase" | "getBlockDifficulty" | "getBlockGasLimit"
+            | "getBlockNumber" | "getBlockTimestamp" => Some(Capability::ReadsBlockContext),
+            _ => None,
+        }
+    }
+}
+
+/// The capabilities of a contract, each with the EEI functions reachable from "main" which grant
+/// it.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CapabilityManifest {
+    capabilities: BTreeMap<Capability, BTreeSet<String>>,
+}
+
+impl CapabilityManifest {
+    /// Classifies the EEI functions a module can call from "main".
+    pub fn analyse(module: &Module) -> Self {
+        let graph = CallGraph::build(module);
+        let mut capabilities: BTreeMap<Capability, BTreeSet<String>> = BTreeMap::new();
+
+        for index in graph.reachable_from_main() {
+            let name = match graph.import_name(index) {
+                Some((EEI_NAMESPACE, name)) => name,
+                _ => continue,
+            };
+            if let Some(capability) = Capability::of(name) {
+                capabilities
+                    .entry(capability)
+                    .or_default()
+                    .insert(name.to_string());
+            }
+        }
+        CapabilityManifest { capabilities }
+    }
+
+    /// Returns the contract's capabilities in a fixed order.
+    pub fn capabilities(&self) -> Vec<Capability> {
+        self.capabilities.keys().cloned().collect()
+    }
+
+    /// Determines whether the contract has a capability.
+    pub fn has(&self, capability: Capability) -> bool {
+        self.capabilities.contains_key(&capability)
+    }
+
+    /// Returns the EEI functions granting a capability, sorted by name.
+    pub fn functions(&self, capability: Capability) -> Vec<&str> {
+        self.capabilities
+            .get(&capability)
+            .map_or(Vec::new(), |names| {
+                names.iter().map(|n| n.as_str()).collect()
+            })
+    }
+
+    /// Determines whether the contract has no capabilities.
+    pub fn is_empty(&self) -> bool {
+        self.capabilities.is_empty()
+    }
+}
+
+impl fmt::Display for CapabilityManifest {
+    /// Writes one line per capability, e.g. "writes-storage: storageStore".
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (capability, names) in &self.capabilities {
+            let names: Vec<&str> = names.iter().map(|n| n.as_str()).collect();
+            writeln!(f, "{}: {}", capability.as_str(), names.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use capabilities::*;
+    use checker::check;
+    use checklist::EciChecklist;
+    use parity_wasm::elements::deserialize_buffer;
+
+    #[test]
+    fn classify() {
+        assert_eq!(Capability::of("callCode"), Some(Capability::DelegateCalls));
+        assert_eq!(
+            Capability::of("getBlockNumber"),
+            Some(Capability::ReadsBlockContext)
+        );
+        assert_eq!(Capability::of("getCallDataSize"), None);
+    }
+
+    #[test]
+    fn manifest() {
+        // main calls storageStore and, through a helper, storageLoad; selfDestruct is only called
+        // by a function nothing calls.
+        let code: Vec<u8> = vec![
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x0d, 0x03, 0x60, 0x02, 0x7f,
+            0x7f, 0x00, 0x60, 0x00, 0x00, 0x60, 0x01, 0x7f, 0x00, 0x02, 0x48, 0x03, 0x08, 0x65,
+            0x74, 0x68, 0x65, 0x72, 0x65, 0x75, 0x6d, 0x0b, 0x73, 0x74, 0x6f, 0x72, 0x61, 0x67,
+            0x65, 0x4c, 0x6f, 0x61, 0x64, 0x00, 0x00, 0x08, 0x65, 0x74, 0x68, 0x65, 0x72, 0x65,
+            0x75, 0x6d, 0x0c, 0x73, 0x74, 0x6f, 0x72, 0x61, 0x67, 0x65, 0x53, 0x74, 0x6f, 0x72,
+            0x65, 0x00, 0x00, 0x08, 0x65, 0x74, 0x68, 0x65, 0x72, 0x65, 0x75, 0x6d, 0x0c, 0x73,
+            0x65, 0x6c, 0x66, 0x44, 0x65, 0x73, 0x74, 0x72, 0x75, 0x63, 0x74, 0x00, 0x02, 0x03,
+            0x04, 0x03, 0x01, 0x01, 0x01, 0x05, 0x03, 0x01, 0x00, 0x01, 0x07, 0x11, 0x02, 0x04,
+            0x6d, 0x61, 0x69, 0x6e, 0x00, 0x03, 0x06, 0x6d, 0x65, 0x6d, 0x6f, 0x72, 0x79, 0x02,
+            0x00, 0x0a, 0x1c, 0x03, 0x0a, 0x00, 0x41, 0x00, 0x41, 0x20, 0x10, 0x01, 0x10, 0x04,
+            0x0b, 0x08, 0x00, 0x41, 0x00, 0x41, 0x20, 0x10, 0x00, 0x0b, 0x06, 0x00, 0x41, 0x00,
+            0x10, 0x02, 0x0b,
+        ];
+        let module = deserialize_buffer::<Module>(&code).unwrap();
+        let manifest = CapabilityManifest::analyse(&module);
+        assert_eq!(
+            manifest.capabilities(),
+            vec![Capability::ReadsStorage, Capability::WritesStorage]
+        );
+        assert!(!manifest.has(Capability::SelfDestructs));
+        assert_eq!(
+            manifest.functions(Capability::WritesStorage),
+            vec!["storageStore"]
+        );
+        assert_eq!(
+            manifest.to_string(),
+            "reads-storage: storageLoad\nwrites-storage: storageStore\n"
+        );
+
+        let mut checklist = EciChecklist::new();
+        assert!(check(&code, &checklist).capabilities().is_none());
+        checklist.set_capability_manifest(true);
+        assert_eq!(check(&code, &checklist).capabilities(), Some(&manifest));
+        assert!(check(&code[..12], &checklist).capabilities().is_none());
+    }
+}
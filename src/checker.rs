@@ -22,8 +22,294 @@
  * SOFTWARE.
  */
 
-use checklist::{CheckStatus, EciChecklist};
+use capabilities::CapabilityManifest;
+use checklist::{CheckContext, CheckResult, CheckStatus, EciChecklist, Finding};
+use limits::LimitExceeded;
 use parity_wasm::elements::{deserialize_buffer, Module};
+use std::collections::HashMap;
+use std::fmt;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::time::{Duration, Instant};
+use structure::{scan, StructureError};
+
+/// Errors preventing a module from being checked at all.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ModuleError {
+    /// The bytecode's header or section layout is invalid.
+    Structure(StructureError),
+    /// The bytecode could not be deserialized into a module.
+    Deserialize(String),
+    /// The module exceeds one of the checklist's resource limits.
+    ResourceLimitExceeded(LimitExceeded),
+    /// A check panicked while running against the module.
+    Panicked(String),
+}
+
+impl fmt::Display for ModuleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ModuleError::Structure(ref err) => write!(f, "malformed module: {}", err),
+            ModuleError::Deserialize(ref msg) => write!(f, "failed to deserialize module: {}", msg),
+            ModuleError::ResourceLimitExceeded(ref limit) => write!(f, "{}", limit),
+            ModuleError::Panicked(ref msg) => write!(f, "check panicked: {}", msg),
+        }
+    }
+}
+
+/// Outcome of running a checklist against a single module.
+#[derive(Clone, Debug)]
+pub struct Report {
+    results: HashMap<String, CheckResult>,
+    timings: HashMap<String, Duration>,
+    error: Option<ModuleError>,
+    capabilities: Option<CapabilityManifest>,
+}
+
+impl Report {
+    /// Constructs a report with every check in the checklist marked as "Unknown."
+    pub(crate) fn pending(checklist: &EciChecklist) -> Self {
+        Report {
+            results: checklist
+                .checklist
+                .keys()
+                .map(|id| (id.clone(), CheckStatus::Unknown.into()))
+                .collect(),
+            timings: HashMap::new(),
+            error: None,
+            capabilities: None,
+        }
+    }
+
+    /// Constructs a report for a module that could not be checked. If a resource limit was
+    /// exceeded, every check is marked as such.
+    pub(crate) fn failed(checklist: &EciChecklist, error: ModuleError) -> Self {
+        let mut report = Report::pending(checklist);
+        if let ModuleError::ResourceLimitExceeded(_) = error {
+            for result in report.results.values_mut() {
+                result.status = CheckStatus::ResourceLimitExceeded;
+            }
+        }
+        report.error = Some(error);
+        report
+    }
+
+    /// Records the capabilities of the checked module.
+    pub(crate) fn set_capabilities(&mut self, capabilities: CapabilityManifest) {
+        self.capabilities = Some(capabilities);
+    }
+
+    /// Records the outcome of a single check and the time it took.
+    pub(crate) fn record(&mut self, key: &str, result: CheckResult, elapsed: Duration) {
+        self.results.insert(key.to_string(), result);
+        self.timings.insert(key.to_string(), elapsed);
+    }
+
+    /// Returns the status of a check, or None if the check was not part of the checklist.
+    pub fn status(&self, key: &str) -> Option<CheckStatus> {
+        self.results.get(key).map(|result| result.status)
+    }
+
+    /// Returns the findings reported by a check, or None if the check was not part of the
+    /// checklist.
+    pub fn findings(&self, key: &str) -> Option<&[Finding]> {
+        self.results.get(key).map(|result| &result.findings[..])
+    }
+
+    /// Returns the time taken by a check, or None if the check did not run.
+    pub fn elapsed(&self, key: &str) -> Option<Duration> {
+        self.timings.get(key).cloned()
+    }
+
+    /// Returns the time taken by each check that ran.
+    pub fn timings(&self) -> &HashMap<String, Duration> {
+        &self.timings
+    }
+
+    /// Returns the error that prevented the checks from running, if any.
+    pub fn error(&self) -> Option<&ModuleError> {
+        self.error.as_ref()
+    }
+
+    /// Returns true if the module was checked and every check is good.
+    pub fn is_good(&self) -> bool {
+        self.error.is_none()
+            && self
+                .results
+                .values()
+                .all(|result| result.status == CheckStatus::Good)
+    }
+
+    /// Returns a vector containing each check and its respective status.
+    pub fn dump_checks(&self) -> Vec<(String, CheckStatus)> {
+        self.results
+            .iter()
+            .map(|(id, result)| (id.clone(), result.status))
+            .collect()
+    }
+
+    /// Returns the capabilities of the module, or None if the checklist does not ask for them or
+    /// the module could not be checked.
+    pub fn capabilities(&self) -> Option<&CapabilityManifest> {
+        self.capabilities.as_ref()
+    }
+
+    /// Writes the status, time taken and findings of each check to stdout.
+    pub fn print(&self) {
+        if let Some(ref error) = self.error {
+            println!("ERROR: {}", error);
+        }
+
+        let mut ids: Vec<&String> = self.results.keys().collect();
+        ids.sort();
+        for id in ids {
+            let result = &self.results[id];
+            match self.timings.get(id) {
+                Some(elapsed) => println!(
+                    "ID: {} - STATUS: {} - TIME: {:?}",
+                    id,
+                    result.status.as_str(),
+                    elapsed
+                ),
+                None => println!("ID: {} - STATUS: {}", id, result.status.as_str()),
+            }
+            for finding in result.findings.iter() {
+                println!("    {}: {}", finding.severity().as_str(), finding.message());
+            }
+        }
+
+        if let Some(ref capabilities) = self.capabilities {
+            println!("CAPABILITIES:");
+            for capability in capabilities.capabilities() {
+                println!(
+                    "    {}: {}",
+                    capability.as_str(),
+                    capabilities.functions(capability).join(", ")
+                );
+            }
+        }
+    }
+}
+
+/// Deserializes the borrowed WASM code and executes every check in the checklist against it.
+/// Neither the code nor the checklist is modified, so one checklist can be shared between threads.
+pub fn check(code: &[u8], checklist: &EciChecklist) -> Report {
+    let ids: Vec<&str> = checklist.checklist.keys().map(|id| id.as_str()).collect();
+    check_with(code, checklist, |module| {
+        run_checks(module, code, checklist, &ids)
+    })
+}
+
+/// Prepares the code and records the results of running the checklist against it with the given
+/// runner. A panic while checking fails the report rather than propagating.
+pub(crate) fn check_with<F>(code: &[u8], checklist: &EciChecklist, run: F) -> Report
+where
+    F: FnOnce(&Module) -> Vec<(String, CheckResult, Duration)>,
+{
+    let start = Instant::now();
+
+    let checked = catch_unwind(AssertUnwindSafe(|| {
+        let module = prepare(code, checklist)?;
+        let mut report = Report::pending(checklist);
+        for (id, result, elapsed) in run(&module) {
+            report.record(&id, result, elapsed);
+        }
+        if checklist.capability_manifest() {
+            report.set_capabilities(CapabilityManifest::analyse(&module));
+        }
+        Ok(report)
+    }))
+    .unwrap_or_else(|payload| {
+        let msg = match payload.downcast_ref::<&str>() {
+            Some(msg) => msg.to_string(),
+            None => payload
+                .downcast_ref::<String>()
+                .cloned()
+                .unwrap_or_else(|| "unknown panic".to_string()),
+        };
+        Err(ModuleError::Panicked(msg))
+    });
+
+    let report = match checked {
+        Ok(report) => report,
+        Err(e) => {
+            warn!("module rejected: {}", e);
+            Report::failed(checklist, e)
+        }
+    };
+
+    debug!(
+        "module checked: bytes={} checks={} good={} duration={:?}",
+        code.len(),
+        checklist.checklist.len(),
+        report.is_good(),
+        start.elapsed()
+    );
+    report
+}
+
+/// Deserializes borrowed WASM code into a module, enforcing the checklist's resource limits
+/// before deserialization. The module's structure is scanned before it is handed to the full
+/// parser, so that framing errors are reported with their byte offset and the sections it finds
+/// can be measured against the limits without decoding them.
+pub(crate) fn prepare(code: &[u8], checklist: &EciChecklist) -> Result<Module, ModuleError> {
+    let limits = checklist.limits();
+
+    limits
+        .check_input(code)
+        .map_err(ModuleError::ResourceLimitExceeded)?;
+    let sections = scan(code).map_err(ModuleError::Structure)?;
+    limits.check_sections(code, &sections)?;
+    deserialize_buffer::<Module>(code).map_err(|e| ModuleError::Deserialize(e.to_string()))
+}
+
+/// Runs the given subset of a checklist against a decoded module, timing and logging each check.
+/// A check which overruns the checklist's time budget has its result replaced once it returns;
+/// checks are not interrupted.
+pub(crate) fn run_checks(
+    module: &Module,
+    code: &[u8],
+    checklist: &EciChecklist,
+    ids: &[&str],
+) -> Vec<(String, CheckResult, Duration)> {
+    let ctx = CheckContext::new(module, code, checklist.interface(), checklist.profile());
+
+    ids.iter()
+        .map(|id| {
+            trace!("check started: id={}", id);
+            let start = Instant::now();
+            let mut result = checklist.get_checker(id).run(&ctx);
+            let elapsed = start.elapsed();
+
+            if let Some(budget) = checklist.limits().check_time_budget {
+                if elapsed > budget {
+                    warn!(
+                        "check over budget: id={} duration={:?} budget={:?}",
+                        id, elapsed, budget
+                    );
+                    result = CheckResult::new(
+                        CheckStatus::ResourceLimitExceeded,
+                        vec![Finding::new(&format!(
+                            "check took {:?}, exceeding its budget of {:?}",
+                            elapsed, budget
+                        ))],
+                    );
+                }
+            }
+
+            debug!(
+                "check finished: id={} status={} findings={} duration={:?}",
+                id,
+                result.status.as_str(),
+                result.findings.len(),
+                elapsed
+            );
+            for finding in result.findings.iter() {
+                trace!("check finding: id={} message={}", id, finding.message());
+            }
+            (id.to_string(), result, elapsed)
+        })
+        .collect()
+}
 
 #[derive(Clone)]
 ///Structure initialized with inputted WASM code and a checklist struct.
@@ -34,6 +320,7 @@ pub struct EcicChecker {
 
 impl EcicChecker {
     /// Constructs an empty checker struct with no code and no checks.
+    #[allow(clippy::new_without_default)]
     pub fn new() -> Self {
         EcicChecker::empty()
     }
@@ -47,9 +334,10 @@ impl EcicChecker {
     }
 
     /// Initializes with inputted code and the default ECI checks.
-    pub fn default(input: &Vec<u8>) -> Self {
+    #[allow(clippy::should_implement_trait)]
+    pub fn default(input: &[u8]) -> Self {
         EcicChecker {
-            code: input.clone(),
+            code: input.to_vec(),
             checks: EciChecklist::default(),
         }
     }
@@ -59,40 +347,43 @@ impl EcicChecker {
         self.code.len()
     }
 
-    /// Deserializes the WASM code and executes all checks in the checklist.
-    pub fn fire(&mut self) {
-        let module = deserialize_buffer::<Module>(&mut self.code).unwrap();
-
-        let check_ids: Vec<String> = self.checks.checklist.keys().map(|x| x.clone()).collect();
+    /// Executes all checks in the checklist and records their statuses in it.
+    pub fn fire(&mut self) -> Report {
+        trace!("firing checker: bytes={}", self.code.len());
+        let report = check(&self.code, &self.checks);
 
-        for check in check_ids {
-            let checkresult = self.checks.get_checker(check.as_str())(&module);
-            self.checks.set_check_status(check.as_str(), checkresult);
+        for (id, status) in report.dump_checks() {
+            self.checks.set_check_status(id.as_str(), status);
         }
+        report
     }
 
     /// Writes a brief report of each check to stdout.
     pub fn print_report(&self) {
         for (id, val) in self.checks.dump_checks().iter() {
-            println!(
-                "ID: {} - STATUS: {}",
-                id,
-                match val {
-                    // TODO: Refactor this into a method on CheckStatus
-                    CheckStatus::Unknown => "Unknown",
-                    CheckStatus::Nonexistent => "Nonexistent",
-                    CheckStatus::Malformed => "Malformed",
-                    CheckStatus::Good => "Good",
-                }
-            )
+            println!("ID: {} - STATUS: {}", id, val.as_str())
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use checker::EcicChecker;
-    use checklist::CheckStatus;
+    use checker::{check, EcicChecker, ModuleError};
+    use checklist::{CheckContext, CheckResult, CheckStatus, EciChecklist, Severity};
+    use eci_std;
+    use eei::ImportInterfaceMap;
+    use limits::{Limit, ResourceLimits};
+    use metering::{inject_metering, OpcodeCosts};
+    use parity_wasm::elements::{deserialize_buffer, BlockType, Instruction, Internal, Module};
+    use profile::Profile;
+    use revision::RevisionReport;
+    use spec::InterfaceSpec;
+    use stack_height::{detect_stack_limit, frame_cost, limit_stack_height};
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+    use structure::StructureErrorKind;
+    use transform::TransformError;
 
     #[test]
     fn empty_code() {
@@ -333,4 +624,620 @@ mod tests {
             CheckStatus::Malformed
         );
     }
+
+    #[test]
+    fn test_funcsigs_undescribed_namespace() {
+        // Imports env.getBalance.
+        let wasm: Vec<u8> = vec![
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x09, 0x02, 0x60, 0x02, 0x7f,
+            0x7f, 0x00, 0x60, 0x00, 0x00, 0x02, 0x12, 0x01, 0x03, 0x65, 0x6e, 0x76, 0x0a, 0x67,
+            0x65, 0x74, 0x42, 0x61, 0x6c, 0x61, 0x6e, 0x63, 0x65, 0x00, 0x00, 0x03, 0x02, 0x01,
+            0x01, 0x07, 0x08, 0x01, 0x04, 0x6d, 0x61, 0x69, 0x6e, 0x00, 0x01, 0x0a, 0x04, 0x01,
+            0x02, 0x00, 0x0b,
+        ];
+        let report = check(&wasm, &EciChecklist::default());
+        assert_eq!(report.status("eei-funcsigs"), Some(CheckStatus::Malformed));
+        assert_eq!(
+            report.findings("eei-funcsigs").unwrap()[0].message(),
+            "env.getBalance is imported from namespace \"env\", which EEI revision 3 does not describe"
+        );
+    }
+
+    #[test]
+    fn test_check_borrowed() {
+        let wasm: Vec<u8> = vec![
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x04, 0x01, 0x60, 0x00, 0x00,
+            0x03, 0x02, 0x01, 0x00, 0x07, 0x08, 0x01, 0x04, 0x6d, 0x61, 0x69, 0x6e, 0x00, 0x00,
+            0x0a, 0x04, 0x01, 0x02, 0x00, 0x0b,
+        ];
+        let checklist = EciChecklist::default();
+        let report = check(&wasm, &checklist);
+        assert!(report.error().is_none());
+        assert_eq!(report.status("export-main"), Some(CheckStatus::Good));
+        assert_eq!(
+            report.status("export-memory"),
+            Some(CheckStatus::Nonexistent)
+        );
+        assert_eq!(report.status("no-such-check"), None);
+        assert!(!report.is_good());
+        assert_eq!(
+            checklist.get_check_status("export-main"),
+            CheckStatus::Unknown
+        );
+    }
+
+    #[test]
+    fn test_check_undecodable() {
+        let wasm: Vec<u8> = vec![0x00, 0x77, 0x61, 0x73, 0x6d];
+        let report = check(&wasm, &EciChecklist::default());
+        match report.error() {
+            Some(ModuleError::Structure(err)) => assert_eq!(err.kind, StructureErrorKind::BadMagic),
+            _ => panic!("expected a structure error"),
+        }
+        assert_eq!(report.status("export-main"), Some(CheckStatus::Unknown));
+    }
+
+    #[test]
+    fn test_check_shared_checklist() {
+        let wasm: Arc<Vec<u8>> = Arc::new(vec![
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x05, 0x03, 0x01, 0x00, 0x00, 0x07,
+            0x0a, 0x01, 0x06, 0x6d, 0x65, 0x6d, 0x6f, 0x72, 0x79, 0x02, 0x00,
+        ]);
+        let checklist = Arc::new(EciChecklist::default());
+
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let wasm = wasm.clone();
+                let checklist = checklist.clone();
+                thread::spawn(move || check(&wasm, &checklist))
+            })
+            .collect();
+
+        for handle in handles {
+            let report = handle.join().unwrap();
+            assert_eq!(report.status("export-memory"), Some(CheckStatus::Good));
+        }
+    }
+
+    #[test]
+    fn test_check_findings_and_timings() {
+        let wasm: Vec<u8> = vec![
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x09, 0x02, 0x60, 0x02, 0x7f,
+            0x7f, 0x00, 0x60, 0x00, 0x00, 0x02, 0x12, 0x01, 0x03, 0x65, 0x6e, 0x76, 0x0a, 0x67,
+            0x65, 0x74, 0x42, 0x61, 0x6c, 0x61, 0x6e, 0x63, 0x65, 0x00, 0x00, 0x03, 0x02, 0x01,
+            0x01, 0x07, 0x08, 0x01, 0x04, 0x6d, 0x61, 0x69, 0x6e, 0x00, 0x01, 0x0a, 0x04, 0x01,
+            0x02, 0x00, 0x0b,
+        ];
+        let report = check(&wasm, &EciChecklist::default());
+        let findings = report.findings("eei-namespace").unwrap();
+        assert_eq!(findings.len(), 1);
+        assert!(findings[0].message().contains("env.getBalance"));
+        assert!(report.findings("export-main").unwrap().is_empty());
+        assert!(report.elapsed("export-main").is_some());
+        assert!(report.elapsed("eei-namespace").is_some());
+        assert!(report.elapsed("eei-funcsigs").is_some());
+        assert!(report.elapsed("debug-imports").is_some());
+    }
+
+    #[test]
+    fn test_funcsigs_type_index_differs_from_import_index() {
+        // Imports ethereum.getCaller with type 1 and ethereum.finish with type 2.
+        let wasm: Vec<u8> = vec![
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x0d, 0x03, 0x60, 0x00, 0x00,
+            0x60, 0x01, 0x7f, 0x00, 0x60, 0x02, 0x7f, 0x7f, 0x00, 0x02, 0x28, 0x02, 0x08, 0x65,
+            0x74, 0x68, 0x65, 0x72, 0x65, 0x75, 0x6d, 0x09, 0x67, 0x65, 0x74, 0x43, 0x61, 0x6c,
+            0x6c, 0x65, 0x72, 0x00, 0x01, 0x08, 0x65, 0x74, 0x68, 0x65, 0x72, 0x65, 0x75, 0x6d,
+            0x06, 0x66, 0x69, 0x6e, 0x69, 0x73, 0x68, 0x00, 0x02,
+        ];
+        let report = check(&wasm, &EciChecklist::default());
+        assert!(report.error().is_none());
+        assert_eq!(report.status("eei-funcsigs"), Some(CheckStatus::Good));
+    }
+
+    #[test]
+    fn test_function_limit_exceeded() {
+        let wasm: Vec<u8> = vec![
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x04, 0x01, 0x60, 0x00, 0x00,
+            0x03, 0x02, 0x01, 0x00, 0x07, 0x08, 0x01, 0x04, 0x6d, 0x61, 0x69, 0x6e, 0x00, 0x00,
+            0x0a, 0x04, 0x01, 0x02, 0x00, 0x0b,
+        ];
+        let mut checklist = EciChecklist::default();
+        checklist.set_limits(ResourceLimits {
+            max_functions: Some(0),
+            ..ResourceLimits::default()
+        });
+        let report = check(&wasm, &checklist);
+        match report.error() {
+            Some(ModuleError::ResourceLimitExceeded(exceeded)) => {
+                assert_eq!(exceeded.limit, Limit::Functions);
+                assert_eq!(exceeded.actual, 1);
+            }
+            _ => panic!("expected the function limit to be exceeded"),
+        }
+        assert_eq!(
+            report.status("export-main"),
+            Some(CheckStatus::ResourceLimitExceeded)
+        );
+    }
+
+    #[test]
+    fn test_input_limit_exceeded() {
+        let wasm: Vec<u8> = vec![0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
+        let mut checklist = EciChecklist::default();
+        checklist.set_limits(ResourceLimits {
+            max_input_bytes: Some(7),
+            ..ResourceLimits::default()
+        });
+        match check(&wasm, &checklist).error() {
+            Some(ModuleError::ResourceLimitExceeded(exceeded)) => {
+                assert_eq!(exceeded.limit, Limit::InputBytes)
+            }
+            _ => panic!("expected the input limit to be exceeded"),
+        }
+    }
+
+    fn slow_check(_ctx: &CheckContext) -> CheckResult {
+        thread::sleep(Duration::from_millis(5));
+        CheckStatus::Good.into()
+    }
+
+    #[test]
+    fn test_check_time_budget() {
+        let wasm: Vec<u8> = vec![0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
+        let mut checklist = EciChecklist::new();
+        checklist.add_detailed_check("slow", slow_check);
+        checklist.set_limits(ResourceLimits {
+            check_time_budget: Some(Duration::from_millis(1)),
+            ..ResourceLimits::default()
+        });
+        let report = check(&wasm, &checklist);
+        assert!(report.error().is_none());
+        assert_eq!(
+            report.status("slow"),
+            Some(CheckStatus::ResourceLimitExceeded)
+        );
+        assert_eq!(report.findings("slow").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_check_deserialize_error() {
+        // Well-framed type section containing an unknown function form.
+        let wasm: Vec<u8> = vec![
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x04, 0x01, 0x61, 0x00, 0x00,
+        ];
+        match check(&wasm, &EciChecklist::default()).error() {
+            Some(ModuleError::Deserialize(_)) => (),
+            _ => panic!("expected a deserialization error"),
+        }
+    }
+
+    #[test]
+    fn test_check_section_overrun() {
+        let wasm: Vec<u8> = vec![
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x09, 0x01, 0x60, 0x00, 0x00,
+        ];
+        let report = check(&wasm, &EciChecklist::default());
+        match report.error() {
+            Some(ModuleError::Structure(err)) => assert_eq!(err.offset, 8),
+            _ => panic!("expected a structure error"),
+        }
+        assert_eq!(
+            report.error().unwrap().to_string(),
+            "malformed module: section 1 of size 9 overruns the 4 remaining bytes at byte 8"
+        );
+    }
+
+    #[test]
+    fn test_canonical_encoding_check() {
+        // The "main" export index is encoded in two bytes.
+        let wasm: Vec<u8> = vec![
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x04, 0x01, 0x60, 0x00, 0x00,
+            0x03, 0x02, 0x01, 0x00, 0x07, 0x09, 0x01, 0x04, 0x6d, 0x61, 0x69, 0x6e, 0x00, 0x80,
+            0x00, 0x0a, 0x04, 0x01, 0x02, 0x00, 0x0b,
+        ];
+        let mut checklist = EciChecklist::default();
+        checklist.add_detailed_check("canonical-encoding", eci_std::chk_canonical_encoding);
+        let report = check(&wasm, &checklist);
+        assert_eq!(report.status("export-main"), Some(CheckStatus::Good));
+        assert_eq!(
+            report.status("canonical-encoding"),
+            Some(CheckStatus::Malformed)
+        );
+        let findings = report.findings("canonical-encoding").unwrap();
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].offset(), Some(27));
+    }
+
+    #[test]
+    fn test_funcsigs_against_revision() {
+        // Imports ethereum.getBalance, which the test interface removes in revision 3.
+        let wasm: Vec<u8> = vec![
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x09, 0x02, 0x60, 0x02, 0x7f,
+            0x7f, 0x00, 0x60, 0x00, 0x00, 0x02, 0x17, 0x01, 0x08, 0x65, 0x74, 0x68, 0x65, 0x72,
+            0x65, 0x75, 0x6d, 0x0a, 0x67, 0x65, 0x74, 0x42, 0x61, 0x6c, 0x61, 0x6e, 0x63, 0x65,
+            0x00, 0x00, 0x03, 0x02, 0x01, 0x01, 0x07, 0x08, 0x01, 0x04, 0x6d, 0x61, 0x69, 0x6e,
+            0x00, 0x01, 0x0a, 0x04, 0x01, 0x02, 0x00, 0x0b,
+        ];
+        let spec = InterfaceSpec::parse(
+            "revision 2\n\
+             namespace ethereum\n\
+             getBalance(i32, i32)\n\
+             revision 3\n\
+             namespace ethereum\n\
+             remove getBalance\n\
+             getExternalBalance(i32, i32)",
+        )
+        .unwrap();
+        let mut checklist = EciChecklist::default();
+        checklist.set_interface(spec.build(3));
+        let report = check(&wasm, &checklist);
+        assert_eq!(report.status("eei-funcsigs"), Some(CheckStatus::Malformed));
+        assert_eq!(
+            report.findings("eei-funcsigs").unwrap()[0].message(),
+            "ethereum.getBalance was removed in EEI revision 3"
+        );
+
+        checklist.set_interface(spec.build(2));
+        let report = check(&wasm, &checklist);
+        assert_eq!(report.status("eei-funcsigs"), Some(CheckStatus::Good));
+    }
+
+    #[test]
+    fn test_namespaces_per_profile() {
+        // Imports debug.print32 with the correct signature and debug.print64 with an i32.
+        let wasm: Vec<u8> = vec![
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x08, 0x02, 0x60, 0x01, 0x7f,
+            0x00, 0x60, 0x00, 0x00, 0x02, 0x21, 0x02, 0x05, 0x64, 0x65, 0x62, 0x75, 0x67, 0x07,
+            0x70, 0x72, 0x69, 0x6e, 0x74, 0x33, 0x32, 0x00, 0x00, 0x05, 0x64, 0x65, 0x62, 0x75,
+            0x67, 0x07, 0x70, 0x72, 0x69, 0x6e, 0x74, 0x36, 0x34, 0x00, 0x00, 0x03, 0x02, 0x01,
+            0x01, 0x07, 0x08, 0x01, 0x04, 0x6d, 0x61, 0x69, 0x6e, 0x00, 0x02, 0x0a, 0x04, 0x01,
+            0x02, 0x00, 0x0b,
+        ];
+        let mut checklist = EciChecklist::default();
+        let report = check(&wasm, &checklist);
+        assert_eq!(report.status("eei-namespace"), Some(CheckStatus::Malformed));
+        assert_eq!(
+            report.findings("eei-namespace").unwrap()[0].message(),
+            "debug.print32 is imported from namespace \"debug\", which profile \"default\" does not permit"
+        );
+        let findings = report.findings("eei-funcsigs").unwrap();
+        assert_eq!(findings.len(), 1);
+        assert_eq!(
+            findings[0].message(),
+            "debug.print64 has signature (i32), expected (i64)"
+        );
+        assert_eq!(findings[0].function(), Some(1));
+
+        checklist.set_profile(
+            Profile::new("testing")
+                .permit_namespace("ethereum")
+                .permit_namespace("debug"),
+        );
+        let report = check(&wasm, &checklist);
+        assert_eq!(report.status("eei-namespace"), Some(CheckStatus::Good));
+    }
+
+    #[test]
+    fn test_debug_imports_per_mode() {
+        // Imports debug.print32 and calls it from main.
+        let wasm: Vec<u8> = vec![
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x08, 0x02, 0x60, 0x01, 0x7f,
+            0x00, 0x60, 0x00, 0x00, 0x02, 0x11, 0x01, 0x05, 0x64, 0x65, 0x62, 0x75, 0x67, 0x07,
+            0x70, 0x72, 0x69, 0x6e, 0x74, 0x33, 0x32, 0x00, 0x00, 0x03, 0x02, 0x01, 0x01, 0x07,
+            0x08, 0x01, 0x04, 0x6d, 0x61, 0x69, 0x6e, 0x00, 0x01, 0x0a, 0x08, 0x01, 0x06, 0x00,
+            0x41, 0x2a, 0x10, 0x00, 0x0b,
+        ];
+        let mut checklist = EciChecklist::default();
+        checklist.set_profile(Profile::production());
+        let report = check(&wasm, &checklist);
+        assert_eq!(report.findings("eei-namespace").unwrap().len(), 1);
+        assert_eq!(report.status("debug-imports"), Some(CheckStatus::Malformed));
+        let findings = report.findings("debug-imports").unwrap();
+        assert_eq!(findings.len(), 1);
+        assert_eq!(
+            findings[0].message(),
+            "debugging function debug.print32(i32) is called from function 1 instruction 1"
+        );
+        assert_eq!(findings[0].severity(), Severity::Error);
+        assert_eq!(findings[0].function(), Some(0));
+
+        checklist.set_profile(Profile::production().permit_namespace("debug"));
+        let report = check(&wasm, &checklist);
+        assert_eq!(report.status("eei-namespace"), Some(CheckStatus::Good));
+        assert_eq!(report.status("debug-imports"), Some(CheckStatus::Malformed));
+        assert_eq!(report.findings("debug-imports").unwrap(), findings);
+
+        checklist.set_profile(Profile::testnet());
+        let report = check(&wasm, &checklist);
+        assert_eq!(report.status("eei-namespace"), Some(CheckStatus::Good));
+        assert_eq!(report.status("debug-imports"), Some(CheckStatus::Good));
+        let findings = report.findings("debug-imports").unwrap();
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].severity(), Severity::Warning);
+    }
+
+    #[test]
+    fn test_deprecated_import_warning() {
+        // Imports ethereum.callCode, which the test interface deprecates in revision 2.
+        let wasm: Vec<u8> = vec![
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x0d, 0x02, 0x60, 0x05, 0x7e,
+            0x7f, 0x7f, 0x7f, 0x7f, 0x01, 0x7f, 0x60, 0x00, 0x00, 0x02, 0x15, 0x01, 0x08, 0x65,
+            0x74, 0x68, 0x65, 0x72, 0x65, 0x75, 0x6d, 0x08, 0x63, 0x61, 0x6c, 0x6c, 0x43, 0x6f,
+            0x64, 0x65, 0x00, 0x00, 0x03, 0x02, 0x01, 0x01, 0x07, 0x08, 0x01, 0x04, 0x6d, 0x61,
+            0x69, 0x6e, 0x00, 0x01, 0x0a, 0x04, 0x01, 0x02, 0x00, 0x0b,
+        ];
+        let mut checklist = EciChecklist::default();
+        let report = check(&wasm, &checklist);
+        assert_eq!(report.status("eei-deprecated"), Some(CheckStatus::Good));
+        assert!(report.findings("eei-deprecated").unwrap().is_empty());
+
+        let interface = ImportInterfaceMap::from_spec(
+            "revision 1\n\
+             namespace ethereum\n\
+             callCode(i64, i32, i32, i32, i32) -> i32\n\
+             callDelegate(i64, i32, i32, i32) -> i32\n\
+             revision 2\n\
+             namespace ethereum\n\
+             deprecate callCode by callDelegate: CALLCODE semantics are superseded by DELEGATECALL",
+        )
+        .unwrap();
+        checklist.set_interface(interface);
+        let report = check(&wasm, &checklist);
+        assert_eq!(report.status("eei-deprecated"), Some(CheckStatus::Good));
+        assert_eq!(report.status("eei-funcsigs"), Some(CheckStatus::Good));
+        let findings = report.findings("eei-deprecated").unwrap();
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].severity(), Severity::Warning);
+        assert_eq!(
+            findings[0].message(),
+            "ethereum.callCode is deprecated since EEI revision 2, use ethereum.callDelegate \
+             instead (CALLCODE semantics are superseded by DELEGATECALL)"
+        );
+    }
+
+    #[test]
+    fn test_memory_bounds() {
+        // One page of memory. main calls getCaller at 65526 and 0, callDataCopy with an unknown
+        // result offset, and callDataCopy writing 1000 bytes at 65000.
+        let wasm: Vec<u8> = vec![
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x0e, 0x03, 0x60, 0x01, 0x7f,
+            0x00, 0x60, 0x00, 0x00, 0x60, 0x03, 0x7f, 0x7f, 0x7f, 0x00, 0x02, 0x2e, 0x02, 0x08,
+            0x65, 0x74, 0x68, 0x65, 0x72, 0x65, 0x75, 0x6d, 0x09, 0x67, 0x65, 0x74, 0x43, 0x61,
+            0x6c, 0x6c, 0x65, 0x72, 0x00, 0x00, 0x08, 0x65, 0x74, 0x68, 0x65, 0x72, 0x65, 0x75,
+            0x6d, 0x0c, 0x63, 0x61, 0x6c, 0x6c, 0x44, 0x61, 0x74, 0x61, 0x43, 0x6f, 0x70, 0x79,
+            0x00, 0x02, 0x03, 0x02, 0x01, 0x01, 0x05, 0x03, 0x01, 0x00, 0x01, 0x07, 0x08, 0x01,
+            0x04, 0x6d, 0x61, 0x69, 0x6e, 0x00, 0x02, 0x0a, 0x24, 0x01, 0x22, 0x01, 0x01, 0x7f,
+            0x41, 0xf6, 0xff, 0x03, 0x10, 0x00, 0x41, 0x00, 0x10, 0x00, 0x20, 0x00, 0x41, 0x00,
+            0x41, 0xe8, 0x07, 0x10, 0x01, 0x41, 0xe8, 0xfb, 0x03, 0x41, 0x00, 0x41, 0xe8, 0x07,
+            0x10, 0x01, 0x0b,
+        ];
+        let report = check(&wasm, &EciChecklist::default());
+        assert_eq!(report.status("memory-bounds"), Some(CheckStatus::Malformed));
+        let findings = report.findings("memory-bounds").unwrap();
+        assert_eq!(findings.len(), 2);
+        assert_eq!(
+            findings[0].message(),
+            "ethereum.getCaller at instruction 1 writes 20 bytes at offset 65526, beyond the \
+             initial memory of 65536 bytes"
+        );
+        assert_eq!(findings[0].function(), Some(2));
+        assert!(findings[1]
+            .message()
+            .starts_with("ethereum.callDataCopy at instruction 11"));
+    }
+
+    #[test]
+    fn test_gas_metering_coverage() {
+        // Imports ethereum.useGas and calls it before, but not inside, a loop.
+        let wasm: Vec<u8> = vec![
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x08, 0x02, 0x60, 0x01, 0x7e,
+            0x00, 0x60, 0x00, 0x00, 0x02, 0x13, 0x01, 0x08, 0x65, 0x74, 0x68, 0x65, 0x72, 0x65,
+            0x75, 0x6d, 0x06, 0x75, 0x73, 0x65, 0x47, 0x61, 0x73, 0x00, 0x00, 0x03, 0x02, 0x01,
+            0x01, 0x05, 0x03, 0x01, 0x00, 0x01, 0x07, 0x11, 0x02, 0x04, 0x6d, 0x61, 0x69, 0x6e,
+            0x00, 0x01, 0x06, 0x6d, 0x65, 0x6d, 0x6f, 0x72, 0x79, 0x02, 0x00, 0x0a, 0x0f, 0x01,
+            0x0d, 0x00, 0x42, 0x02, 0x10, 0x00, 0x03, 0x40, 0x41, 0x00, 0x0d, 0x00, 0x0b, 0x0b,
+        ];
+        let mut checklist = EciChecklist::default();
+        checklist.add_detailed_check("gas-metering", eci_std::chk_gas_metering);
+        let report = check(&wasm, &checklist);
+        assert_eq!(report.status("gas-metering"), Some(CheckStatus::Malformed));
+        let findings = report.findings("gas-metering").unwrap();
+        assert_eq!(findings.len(), 2);
+        assert_eq!(
+            findings[0].message(),
+            "1 of 2 blocks (50.0%) and 0 of 1 loop headers are metered"
+        );
+        assert_eq!(
+            findings[1].message(),
+            "loop header block at instructions 3..5 does not call useGas"
+        );
+        assert_eq!(findings[1].function(), Some(1));
+    }
+
+    #[test]
+    fn test_inject_metering() {
+        // main calls a helper and then spins in a loop; nothing imports useGas.
+        let wasm: Vec<u8> = vec![
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x04, 0x01, 0x60, 0x00, 0x00,
+            0x03, 0x03, 0x02, 0x00, 0x00, 0x05, 0x03, 0x01, 0x00, 0x01, 0x07, 0x11, 0x02, 0x04,
+            0x6d, 0x61, 0x69, 0x6e, 0x00, 0x00, 0x06, 0x6d, 0x65, 0x6d, 0x6f, 0x72, 0x79, 0x02,
+            0x00, 0x0a, 0x11, 0x02, 0x0b, 0x00, 0x10, 0x01, 0x03, 0x40, 0x41, 0x00, 0x0d, 0x00,
+            0x0b, 0x0b, 0x03, 0x00, 0x01, 0x0b,
+        ];
+        let costs = OpcodeCosts::default().with_cost("call", 5);
+        let metered = inject_metering(&wasm, &costs, &EciChecklist::default()).unwrap();
+        let report = metered.report();
+        assert!(report.is_good());
+        assert_eq!(report.status("gas-metering"), Some(CheckStatus::Good));
+
+        let module = deserialize_buffer::<Module>(metered.code()).unwrap();
+        assert_eq!(module.import_section().unwrap().functions(), 1);
+        let bodies = module.code_section().unwrap().bodies();
+        assert_eq!(
+            bodies[0].code().elements(),
+            &[
+                Instruction::I64Const(6),
+                Instruction::Call(0),
+                Instruction::Call(2),
+                Instruction::Loop(BlockType::NoResult),
+                Instruction::I64Const(2),
+                Instruction::Call(0),
+                Instruction::I32Const(0),
+                Instruction::BrIf(0),
+                Instruction::End,
+                Instruction::End,
+            ]
+        );
+        assert_eq!(
+            bodies[1].code().elements(),
+            &[
+                Instruction::I64Const(2),
+                Instruction::Call(0),
+                Instruction::Nop,
+                Instruction::End,
+            ]
+        );
+        assert_eq!(
+            *module.export_section().unwrap().entries()[0].internal(),
+            Internal::Function(1)
+        );
+    }
+
+    #[test]
+    fn test_metering_costs() {
+        // Charges 2 gas for a block of five instructions, then a non-constant amount in a loop.
+        let wasm: Vec<u8> = vec![
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x09, 0x02, 0x60, 0x01, 0x7e,
+            0x00, 0x60, 0x01, 0x7e, 0x00, 0x02, 0x13, 0x01, 0x08, 0x65, 0x74, 0x68, 0x65, 0x72,
+            0x65, 0x75, 0x6d, 0x06, 0x75, 0x73, 0x65, 0x47, 0x61, 0x73, 0x00, 0x00, 0x03, 0x02,
+            0x01, 0x01, 0x0a, 0x13, 0x01, 0x11, 0x00, 0x42, 0x02, 0x10, 0x00, 0x41, 0x00, 0x1a,
+            0x03, 0x40, 0x20, 0x00, 0x10, 0x00, 0x01, 0x0b, 0x0b,
+        ];
+        let mut checklist = EciChecklist::new();
+        checklist.add_detailed_check("metering-costs", eci_std::chk_metering_costs);
+        let report = check(&wasm, &checklist);
+        assert_eq!(
+            report.status("metering-costs"),
+            Some(CheckStatus::Malformed)
+        );
+        let findings = report.findings("metering-costs").unwrap();
+        assert_eq!(
+            findings[0].message(),
+            "block at instructions 0..5 charges 2 gas but costs 3, 1 too little"
+        );
+        assert_eq!(findings[0].function(), Some(1));
+        assert_eq!(findings[1].severity(), Severity::Warning);
+
+        checklist.set_profile(
+            Profile::default().with_opcode_costs(OpcodeCosts::new(0).with_cost("i32.const", 2)),
+        );
+        let report = check(&wasm, &checklist);
+        assert_eq!(report.status("metering-costs"), Some(CheckStatus::Good));
+        assert_eq!(report.findings("metering-costs").unwrap().len(), 1);
+
+        // Metering injected with the profile's costs always charges enough.
+        let metered =
+            inject_metering(&wasm, &OpcodeCosts::default(), &EciChecklist::new()).unwrap();
+        checklist.set_profile(Profile::default());
+        let report = check(metered.code(), &checklist);
+        assert_eq!(report.status("metering-costs"), Some(CheckStatus::Good));
+    }
+
+    #[test]
+    fn test_stack_limit() {
+        // main calls a recursive countdown(n: i32) -> i32 which declares one i64 local.
+        let wasm: Vec<u8> = vec![
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x09, 0x02, 0x60, 0x00, 0x00,
+            0x60, 0x01, 0x7f, 0x01, 0x7f, 0x03, 0x03, 0x02, 0x00, 0x01, 0x05, 0x03, 0x01, 0x00,
+            0x01, 0x07, 0x11, 0x02, 0x04, 0x6d, 0x61, 0x69, 0x6e, 0x00, 0x00, 0x06, 0x6d, 0x65,
+            0x6d, 0x6f, 0x72, 0x79, 0x02, 0x00, 0x0a, 0x1e, 0x02, 0x07, 0x00, 0x41, 0x03, 0x10,
+            0x01, 0x1a, 0x0b, 0x14, 0x01, 0x01, 0x7e, 0x20, 0x00, 0x45, 0x04, 0x40, 0x41, 0x00,
+            0x0f, 0x0b, 0x20, 0x00, 0x41, 0x01, 0x6b, 0x10, 0x01, 0x0b,
+        ];
+        let mut checklist = EciChecklist::default();
+        checklist.add_detailed_check("stack-limit", eci_std::chk_stack_limit);
+        let report = check(&wasm, &checklist);
+        assert_eq!(report.status("stack-limit"), Some(CheckStatus::Malformed));
+
+        let limited = limit_stack_height(&wasm, 1024, &EciChecklist::default()).unwrap();
+        assert!(limited.report().is_good());
+        assert_eq!(
+            limited.report().status("stack-limit"),
+            Some(CheckStatus::Good)
+        );
+
+        let module = deserialize_buffer::<Module>(limited.code()).unwrap();
+        let limit = detect_stack_limit(&module).unwrap();
+        assert_eq!((limit.global, limit.limit), (0, 1024));
+        assert!(limit.uninstrumented.is_empty());
+        assert_eq!(frame_cost(&module, 1), Some(3));
+        let countdown = module.code_section().unwrap().bodies()[1].code().elements();
+        assert_eq!(countdown[1], Instruction::I32Const(3));
+        assert!(countdown.contains(&Instruction::Br(1)));
+        assert!(!countdown.contains(&Instruction::Return));
+
+        assert_eq!(
+            limit_stack_height(limited.code(), 1024, &EciChecklist::default()).unwrap_err(),
+            TransformError::Conflict("module already limits its stack height".to_string())
+        );
+    }
+
+    #[test]
+    fn test_dangling_call() {
+        // main's body is (call 5), but the module defines no function 5.
+        let wasm: Vec<u8> = vec![
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x04, 0x01, 0x60, 0x00, 0x00,
+            0x03, 0x02, 0x01, 0x00, 0x07, 0x08, 0x01, 0x04, 0x6d, 0x61, 0x69, 0x6e, 0x00, 0x00,
+            0x0a, 0x06, 0x01, 0x04, 0x00, 0x10, 0x05, 0x0b,
+        ];
+        let report = check(&wasm, &EciChecklist::default());
+        assert_eq!(
+            report.status("unreachable-functions"),
+            Some(CheckStatus::Malformed)
+        );
+        assert_eq!(
+            report.findings("unreachable-functions").unwrap()[0].message(),
+            "function 0 calls function 5, which does not exist"
+        );
+    }
+
+    #[test]
+    fn test_missing_types() {
+        // Exports the imported useGas as main and has no function section. Its second import,
+        // getGasLeft, refers to type 5, which does not exist.
+        let wasm: Vec<u8> = vec![
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x04, 0x01, 0x60, 0x00, 0x00,
+            0x02, 0x29, 0x02, 0x08, 0x65, 0x74, 0x68, 0x65, 0x72, 0x65, 0x75, 0x6d, 0x06, 0x75,
+            0x73, 0x65, 0x47, 0x61, 0x73, 0x00, 0x00, 0x08, 0x65, 0x74, 0x68, 0x65, 0x72, 0x65,
+            0x75, 0x6d, 0x0a, 0x67, 0x65, 0x74, 0x47, 0x61, 0x73, 0x4c, 0x65, 0x66, 0x74, 0x00,
+            0x05, 0x07, 0x08, 0x01, 0x04, 0x6d, 0x61, 0x69, 0x6e, 0x00, 0x01,
+        ];
+        let report = check(&wasm, &EciChecklist::default());
+        assert!(report.error().is_none());
+        assert_eq!(report.status("export-main"), Some(CheckStatus::Malformed));
+        let findings = report.findings("eei-funcsigs").unwrap();
+        assert_eq!(
+            findings[1].message(),
+            "ethereum.getGasLeft refers to a type which does not exist"
+        );
+        let module = deserialize_buffer::<Module>(&wasm).unwrap();
+        assert!(RevisionReport::detect(&module).compatible().is_empty());
+
+        // Defines main with type 5.
+        let wasm: Vec<u8> = vec![
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x04, 0x01, 0x60, 0x00, 0x00,
+            0x03, 0x02, 0x01, 0x05, 0x07, 0x08, 0x01, 0x04, 0x6d, 0x61, 0x69, 0x6e, 0x00, 0x00,
+            0x0a, 0x04, 0x01, 0x02, 0x00, 0x0b,
+        ];
+        assert_eq!(
+            check(&wasm, &EciChecklist::default()).status("export-main"),
+            Some(CheckStatus::Malformed)
+        );
+        match limit_stack_height(&wasm, 100, &EciChecklist::default()) {
+            Err(TransformError::Invalid(msg)) => {
+                assert_eq!(msg, "function 0 refers to a type which does not exist")
+            }
+            other => panic!("unexpected result {:?}", other.map(|t| t.into_code())),
+        }
+    }
 }
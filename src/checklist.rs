@@ -23,7 +23,10 @@
  */
 
 use eci_std;
+use eei::ImportInterfaceMap;
+use limits::ResourceLimits;
 use parity_wasm::elements::Module;
+use profile::Profile;
 use std::collections::HashMap;
 
 /// Enum describing the state of a check. Always initialized as "Unknown."
@@ -33,72 +36,310 @@ pub enum CheckStatus {
     Nonexistent,
     Malformed,
     Good,
+    ResourceLimitExceeded,
+}
+
+impl CheckStatus {
+    /// Returns a human-readable name for the status.
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            CheckStatus::Unknown => "Unknown",
+            CheckStatus::Nonexistent => "Nonexistent",
+            CheckStatus::Malformed => "Malformed",
+            CheckStatus::Good => "Good",
+            CheckStatus::ResourceLimitExceeded => "ResourceLimitExceeded",
+        }
+    }
+}
+
+/// Describes whether a finding fails its check.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Severity {
+    /// Returns a human-readable name for the severity.
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        }
+    }
+}
+
+/// A single problem reported by a check.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Finding {
+    message: String,
+    severity: Severity,
+    function: Option<u32>,
+    offset: Option<usize>,
+}
+
+impl Finding {
+    /// Constructs an error finding with the given message.
+    pub fn new(message: &str) -> Self {
+        Finding {
+            message: message.to_string(),
+            severity: Severity::Error,
+            function: None,
+            offset: None,
+        }
+    }
+
+    /// Sets the severity of the finding.
+    pub fn with_severity(mut self, severity: Severity) -> Self {
+        self.severity = severity;
+        self
+    }
+
+    /// Attaches the callable index of the function the finding refers to.
+    pub fn in_function(mut self, index: u32) -> Self {
+        self.function = Some(index);
+        self
+    }
+
+    /// Attaches the byte offset within the bytecode the finding refers to.
+    pub fn at_offset(mut self, offset: usize) -> Self {
+        self.offset = Some(offset);
+        self
+    }
+
+    /// Returns the message describing the finding.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Returns the severity of the finding.
+    pub fn severity(&self) -> Severity {
+        self.severity
+    }
+
+    /// Returns the callable index of the function the finding refers to, if any.
+    pub fn function(&self) -> Option<u32> {
+        self.function
+    }
+
+    /// Returns the byte offset within the bytecode the finding refers to, if any.
+    pub fn offset(&self) -> Option<usize> {
+        self.offset
+    }
+}
+
+/// The status of a check along with the findings that explain it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CheckResult {
+    pub status: CheckStatus,
+    pub findings: Vec<Finding>,
+}
+
+impl CheckResult {
+    /// Constructs a result with the given status and findings.
+    pub fn new(status: CheckStatus, findings: Vec<Finding>) -> Self {
+        CheckResult { status, findings }
+    }
+
+    /// Constructs a result which is good if there are no error findings, and malformed otherwise.
+    pub fn from_findings(findings: Vec<Finding>) -> Self {
+        CheckResult {
+            status: if findings.iter().all(|f| f.severity() == Severity::Warning) {
+                CheckStatus::Good
+            } else {
+                CheckStatus::Malformed
+            },
+            findings,
+        }
+    }
+}
+
+impl From<CheckStatus> for CheckResult {
+    fn from(status: CheckStatus) -> Self {
+        CheckResult::new(status, Vec::new())
+    }
+}
+
+/// Everything a check may inspect about the module being checked.
+pub struct CheckContext<'a> {
+    module: &'a Module,
+    code: &'a [u8],
+    interface: &'a ImportInterfaceMap,
+    profile: &'a Profile,
+}
+
+impl<'a> CheckContext<'a> {
+    /// Constructs a context from a decoded module, the bytecode it was decoded from, the host
+    /// interface it is checked against and the profile it is checked for.
+    pub fn new(
+        module: &'a Module,
+        code: &'a [u8],
+        interface: &'a ImportInterfaceMap,
+        profile: &'a Profile,
+    ) -> Self {
+        CheckContext {
+            module,
+            code,
+            interface,
+            profile,
+        }
+    }
+
+    /// Returns the decoded module.
+    pub fn module(&self) -> &'a Module {
+        self.module
+    }
+
+    /// Returns the raw bytecode of the module.
+    pub fn code(&self) -> &'a [u8] {
+        self.code
+    }
+
+    /// Returns the host interface the module is checked against.
+    pub fn interface(&self) -> &'a ImportInterfaceMap {
+        self.interface
+    }
+
+    /// Returns the profile the module is checked for.
+    pub fn profile(&self) -> &'a Profile {
+        self.profile
+    }
+}
+
+/// Function pointer implementing a check.
+#[derive(Copy, Clone)]
+pub enum CheckFn {
+    /// A check which only reports a status.
+    Simple(fn(&Module) -> CheckStatus),
+    /// A check which reports a status along with findings.
+    Detailed(fn(&CheckContext) -> CheckResult),
+}
+
+impl CheckFn {
+    /// Executes the check against the given context.
+    pub fn run(&self, ctx: &CheckContext) -> CheckResult {
+        match *self {
+            CheckFn::Simple(checkfn) => checkfn(ctx.module()).into(),
+            CheckFn::Detailed(checkfn) => checkfn(ctx),
+        }
+    }
 }
 
 /// Struct containing data about the status of a check, and a function pointer implements the check.
 #[derive(Clone)]
 pub struct Check {
     status: CheckStatus,
-    do_check: fn(&Module) -> CheckStatus,
+    do_check: CheckFn,
 }
 
 /// Checklist structure containing a string-to-check map.
 #[derive(Clone)]
 pub struct EciChecklist {
     pub checklist: HashMap<String, Check>,
+    limits: ResourceLimits,
+    interface: ImportInterfaceMap,
+    profile: Profile,
+    capabilities: bool,
 }
 
 impl EciChecklist {
     /// Constructs an empty checklist.
+    #[allow(clippy::new_without_default)]
     pub fn new() -> Self {
         EciChecklist {
             checklist: HashMap::new(),
+            limits: ResourceLimits::default(),
+            interface: ImportInterfaceMap::default(),
+            profile: Profile::default(),
+            capabilities: false,
         }
     }
 
     /// Constructs a checklist with the standard ewasm ECI checks.
+    #[allow(clippy::should_implement_trait)]
     pub fn default() -> Self {
         let checks: HashMap<String, Check> = [
             (
                 "export-main".to_string(),
                 Check {
                     status: CheckStatus::Unknown,
-                    do_check: eci_std::chk_main_exported,
+                    do_check: CheckFn::Simple(eci_std::chk_main_exported),
                 },
             ),
             (
                 "export-memory".to_string(),
                 Check {
                     status: CheckStatus::Unknown,
-                    do_check: eci_std::chk_mem_exported,
+                    do_check: CheckFn::Simple(eci_std::chk_mem_exported),
                 },
             ),
             (
                 "eei-namespace".to_string(),
                 Check {
                     status: CheckStatus::Unknown,
-                    do_check: eci_std::chk_eei_namespace,
+                    do_check: CheckFn::Detailed(eci_std::chk_eei_namespace),
                 },
             ),
             (
                 "no-startfn".to_string(),
                 Check {
                     status: CheckStatus::Unknown,
-                    do_check: eci_std::chk_no_startfn,
+                    do_check: CheckFn::Simple(eci_std::chk_no_startfn),
                 },
             ),
             (
                 "eei-funcsigs".to_string(),
                 Check {
                     status: CheckStatus::Unknown,
-                    do_check: eci_std::chk_func_signatures,
+                    do_check: CheckFn::Detailed(eci_std::chk_func_signatures),
+                },
+            ),
+            (
+                "debug-imports".to_string(),
+                Check {
+                    status: CheckStatus::Unknown,
+                    do_check: CheckFn::Detailed(eci_std::chk_debug_imports),
+                },
+            ),
+            (
+                "eei-deprecated".to_string(),
+                Check {
+                    status: CheckStatus::Unknown,
+                    do_check: CheckFn::Detailed(eci_std::chk_deprecated_imports),
+                },
+            ),
+            (
+                "memory-bounds".to_string(),
+                Check {
+                    status: CheckStatus::Unknown,
+                    do_check: CheckFn::Detailed(eci_std::chk_memory_bounds),
+                },
+            ),
+            (
+                "unreachable-functions".to_string(),
+                Check {
+                    status: CheckStatus::Unknown,
+                    do_check: CheckFn::Detailed(eci_std::chk_unreachable_functions),
+                },
+            ),
+            (
+                "unused-imports".to_string(),
+                Check {
+                    status: CheckStatus::Unknown,
+                    do_check: CheckFn::Detailed(eci_std::chk_unused_imports),
                 },
             ),
         ].iter()
             .cloned()
             .collect();
 
-        EciChecklist { checklist: checks }
+        EciChecklist {
+            checklist: checks,
+            limits: ResourceLimits::default(),
+            interface: ImportInterfaceMap::default(),
+            profile: Profile::default(),
+            capabilities: false,
+        }
     }
 
     /// Adds a check with the given ID and function implementing said check.
@@ -107,32 +348,81 @@ impl EciChecklist {
             key.to_string(),
             Check {
                 status: CheckStatus::Unknown,
-                do_check: checkfn,
+                do_check: CheckFn::Simple(checkfn),
+            },
+        );
+    }
+
+    /// Adds a check with the given ID and function implementing said check, which may report
+    /// findings.
+    pub fn add_detailed_check(&mut self, key: &str, checkfn: fn(&CheckContext) -> CheckResult) {
+        self.checklist.insert(
+            key.to_string(),
+            Check {
+                status: CheckStatus::Unknown,
+                do_check: CheckFn::Detailed(checkfn),
             },
         );
     }
 
     /// Sets the status of a check.
     pub fn set_check_status(&mut self, key: &str, val: CheckStatus) {
-        self.checklist.get_mut(&key.to_string()).unwrap().status = val;
+        self.checklist.get_mut(key).unwrap().status = val;
     }
 
     /// Returns the status of a check.
     pub fn get_check_status(&self, key: &str) -> CheckStatus {
-        self.checklist[&key.to_string()].status.clone()
+        self.checklist[key].status
     }
 
     /// Returns a simple boolean value describing whether a check is good or not.
     pub fn check_is_good(&self, key: &str) -> bool {
-        match self.get_check_status(key) {
-            CheckStatus::Good => true,
-            _ => false,
-        }
+        self.get_check_status(key) == CheckStatus::Good
     }
 
     /// Returns a function pointer to the implementation of a check.
-    pub fn get_checker(&self, key: &str) -> fn(&Module) -> CheckStatus {
-        self.checklist[&key.to_string()].do_check
+    pub fn get_checker(&self, key: &str) -> CheckFn {
+        self.checklist[key].do_check
+    }
+
+    /// Sets the resource limits enforced when checking a module.
+    pub fn set_limits(&mut self, limits: ResourceLimits) {
+        self.limits = limits;
+    }
+
+    /// Returns the resource limits enforced when checking a module.
+    pub fn limits(&self) -> &ResourceLimits {
+        &self.limits
+    }
+
+    /// Sets the host interface, e.g. a specific EEI revision, which imports are checked against.
+    pub fn set_interface(&mut self, interface: ImportInterfaceMap) {
+        self.interface = interface;
+    }
+
+    /// Returns the host interface which imports are checked against.
+    pub fn interface(&self) -> &ImportInterfaceMap {
+        &self.interface
+    }
+
+    /// Sets the profile, e.g. which import namespaces are permitted, a module is checked for.
+    pub fn set_profile(&mut self, profile: Profile) {
+        self.profile = profile;
+    }
+
+    /// Returns the profile a module is checked for.
+    pub fn profile(&self) -> &Profile {
+        &self.profile
+    }
+
+    /// Sets whether reports include a capability manifest of the checked module.
+    pub fn set_capability_manifest(&mut self, enabled: bool) {
+        self.capabilities = enabled;
+    }
+
+    /// Determines whether reports include a capability manifest of the checked module.
+    pub fn capability_manifest(&self) -> bool {
+        self.capabilities
     }
 
     /// Returns a vector containing each check and its respective status.
@@ -155,6 +445,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::unnecessary_to_owned)]
     fn default_checks() {
         let checks = EciChecklist::default();
         assert!(checks.checklist.contains_key(&"export-main".to_string()));
@@ -163,14 +454,13 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::unnecessary_to_owned)]
     fn insert_arbitrary_check() {
         let mut checks = EciChecklist::new();
         checks.add_check("random-arbitrary-check", eci_std::chk_main_exported);
-        assert!(
-            checks
-                .checklist
-                .contains_key(&"random-arbitrary-check".to_string())
-        );
+        assert!(checks
+            .checklist
+            .contains_key(&"random-arbitrary-check".to_string()));
     }
 
     #[test]
@@ -192,4 +482,39 @@ mod tests {
         checks.set_check_status("foobar", CheckStatus::Good);
         assert_eq!(checks.get_check_status("foobar"), CheckStatus::Good);
     }
+
+    fn no_findings(_ctx: &CheckContext) -> CheckResult {
+        CheckResult::from_findings(Vec::new())
+    }
+
+    #[test]
+    fn insert_detailed_check() {
+        let mut checks = EciChecklist::new();
+        checks.add_detailed_check("detailed", no_findings);
+        assert!(checks.checklist.contains_key("detailed"));
+        assert_eq!(checks.get_check_status("detailed"), CheckStatus::Unknown);
+    }
+
+    #[test]
+    fn result_from_findings() {
+        assert_eq!(
+            CheckResult::from_findings(Vec::new()).status,
+            CheckStatus::Good
+        );
+        let result = CheckResult::from_findings(vec![Finding::new("bad").in_function(3)]);
+        assert_eq!(result.status, CheckStatus::Malformed);
+        assert_eq!(result.findings[0].function(), Some(3));
+        assert_eq!(result.findings[0].severity(), Severity::Error);
+    }
+
+    #[test]
+    fn warnings_do_not_fail_checks() {
+        let warning = Finding::new("suspicious").with_severity(Severity::Warning);
+        let result = CheckResult::from_findings(vec![warning.clone()]);
+        assert_eq!(result.status, CheckStatus::Good);
+        assert_eq!(result.findings.len(), 1);
+
+        let result = CheckResult::from_findings(vec![warning, Finding::new("bad")]);
+        assert_eq!(result.status, CheckStatus::Malformed);
+    }
 }
@@ -22,51 +22,308 @@
  * SOFTWARE.
  */
 
-use checklist::CheckStatus;
-use eei::ImportInterfaceMap;
+use bounds::{constant_accesses, grows_memory, initial_memory_size};
+use callgraph::CallGraph;
+use canonical::find_non_canonical;
+use checklist::{CheckContext, CheckResult, CheckStatus, Finding, Severity};
+use eei::{ImportInterfaceMap, DEBUG_NAMESPACE};
+use metering::{undercharged_blocks, MeteringCoverage};
 use parity_wasm::elements::{External, FunctionType, Internal, Module};
+use profile::{Mode, Profile};
+use prune::{unreachable_in, unused_in};
 use pwasm::*;
+use stack_height::detect_stack_limit;
 
 /// Checks that the module's "main" function has been exported with no arguments or return values.
 pub fn chk_main_exported(module: &Module) -> CheckStatus {
-    has_func_export(module, "main", FunctionType::default())
+    let status = has_func_export(module, "main", FunctionType::default());
+    trace!("main export: status={}", status.as_str());
+    status
 }
 
 /// Checks that the module's memory segment has been properly exported.
 pub fn chk_mem_exported(module: &Module) -> CheckStatus {
-    match resolve_export_by_name(module, "memory") {
+    let status = match resolve_export_by_name(module, "memory") {
         Some((index, reference)) => if reference == Internal::Memory(index) {
             CheckStatus::Good
         } else {
             CheckStatus::Malformed
         },
         None => CheckStatus::Nonexistent,
-    }
+    };
+    trace!("memory export: status={}", status.as_str());
+    status
 }
 
-/// Checks that the EEI host functions have been imported with the correct namespace.
-pub fn chk_eei_namespace(module: &Module) -> CheckStatus {
-    if has_import_section(module) {
-        imports_only_eei_namespace(module)
+/// Checks that the host functions have been imported from namespaces the profile permits.
+pub fn chk_eei_namespace(ctx: &CheckContext) -> CheckResult {
+    trace!("import namespaces: profile={}", ctx.profile().name());
+    if has_import_section(ctx.module()) {
+        imports_only_permitted_namespaces(ctx.module(), ctx.profile())
     } else {
-        CheckStatus::Good
+        CheckStatus::Good.into()
     }
 }
 
 /// Ensures that a module has not incorrectly specified a start function.
 pub fn chk_no_startfn(module: &Module) -> CheckStatus {
+    trace!("start function: index={:?}", module.start_section());
     match module.start_section() {
         Some(_thing) => CheckStatus::Malformed,
         None => CheckStatus::Good,
     }
 }
 
-/// Verifies that the EEI has been imported with the correct function signatures.
-pub fn chk_func_signatures(module: &Module) -> CheckStatus {
-    if has_import_section(module) {
-        eei_check_func_sigs(module)
+/// Verifies that the EEI has been imported with the correct function signatures. Imports from
+/// namespaces the interface does not describe are reported, unless the profile permits them.
+pub fn chk_func_signatures(ctx: &CheckContext) -> CheckResult {
+    trace!(
+        "import signatures: revision={}",
+        ctx.interface().revision_number()
+    );
+    if has_import_section(ctx.module()) {
+        check_func_sigs(ctx.module(), ctx.interface(), Some(ctx.profile()))
+    } else {
+        CheckStatus::Good.into()
+    }
+}
+
+/// Reports each import from the "debug" namespace along with the call sites using it. Debugging
+/// imports are warnings in testnet mode and errors in production mode, whichever namespaces the
+/// profile permits.
+pub fn chk_debug_imports(ctx: &CheckContext) -> CheckResult {
+    if has_import_section(ctx.module()) {
+        let severity = match ctx.profile().mode() {
+            Mode::Testnet => Severity::Warning,
+            Mode::Production => Severity::Error,
+        };
+        trace!("debug imports: severity={}", severity.as_str());
+        CheckResult::from_findings(
+            debug_imports(ctx.module(), ctx.interface())
+                .into_iter()
+                .map(|f| f.with_severity(severity))
+                .collect(),
+        )
+    } else {
+        CheckStatus::Good.into()
+    }
+}
+
+/// Warns about each imported function which the host interface marks as deprecated.
+pub fn chk_deprecated_imports(ctx: &CheckContext) -> CheckResult {
+    trace!(
+        "deprecated imports: revision={}",
+        ctx.interface().revision_number()
+    );
+    if has_import_section(ctx.module()) {
+        CheckResult::from_findings(deprecated_imports(ctx.module(), ctx.interface()))
     } else {
-        CheckStatus::Good
+        CheckStatus::Good.into()
+    }
+}
+
+/// Verifies that host function calls with constant pointer and length arguments only access the
+/// module's initial memory. Out-of-bounds accesses are warnings if the module grows its memory.
+pub fn chk_memory_bounds(ctx: &CheckContext) -> CheckResult {
+    let module = ctx.module();
+    let memory_size = initial_memory_size(module).unwrap_or(0);
+    let severity = if grows_memory(module) {
+        Severity::Warning
+    } else {
+        Severity::Error
+    };
+    let accesses = constant_accesses(module, ctx.interface());
+    trace!(
+        "memory bounds: accesses={} memory_size={} severity={}",
+        accesses.len(),
+        memory_size,
+        severity.as_str()
+    );
+
+    CheckResult::from_findings(
+        accesses
+            .iter()
+            .filter(|access| access.end() > memory_size)
+            .map(|access| {
+                Finding::new(&format!(
+                    "{} at instruction {} {} {} bytes at offset {}, beyond the initial memory of {} bytes",
+                    access.callee,
+                    access.instruction,
+                    if access.write { "writes" } else { "reads" },
+                    access.length,
+                    access.offset,
+                    memory_size
+                ))
+                .with_severity(severity)
+                .in_function(access.function)
+            })
+            .collect(),
+    )
+}
+
+/// Verifies that every basic block, including every loop header, calls `ethereum.useGas`. Not
+/// part of the default checklist; add it with the ID "gas-metering".
+pub fn chk_gas_metering(ctx: &CheckContext) -> CheckResult {
+    let coverage = MeteringCoverage::analyse(ctx.module(), ctx.interface());
+    trace!("gas metering: coverage={:.1}%", coverage.percentage());
+    if coverage.is_complete() {
+        return CheckStatus::Good.into();
+    }
+
+    let (metered, total) = coverage.block_coverage();
+    let (metered_loops, loops) = coverage.loop_coverage();
+    let mut findings = vec![Finding::new(&format!(
+        "{} of {} blocks ({:.1}%) and {} of {} loop headers are metered",
+        metered,
+        total,
+        coverage.percentage(),
+        metered_loops,
+        loops
+    ))];
+    findings.extend(coverage.unmetered().iter().map(|block| {
+        Finding::new(&format!(
+            "{} at instructions {}..{} does not call useGas",
+            if block.loop_header {
+                "loop header block"
+            } else {
+                "block"
+            },
+            block.start,
+            block.end
+        ))
+        .in_function(block.function)
+    }));
+    CheckResult::from_findings(findings)
+}
+
+/// Warns about defined functions which are unreachable from the module's exports, start function
+/// and table, and so only add to its size. Calls to functions which do not exist are errors.
+pub fn chk_unreachable_functions(ctx: &CheckContext) -> CheckResult {
+    let graph = CallGraph::build(ctx.module());
+    trace!(
+        "unreachable functions: functions={} dangling_calls={}",
+        graph.len(),
+        graph.dangling_calls().len()
+    );
+    let mut findings: Vec<Finding> = graph
+        .dangling_calls()
+        .iter()
+        .map(|&(caller, callee)| {
+            Finding::new(&format!(
+                "function {} calls function {}, which does not exist",
+                caller, callee
+            ))
+            .in_function(caller)
+        })
+        .collect();
+    findings.extend(unreachable_in(&graph).into_iter().map(|index| {
+        Finding::new(&format!(
+            "function {} is never reachable from an export, the start function or the table",
+            index
+        ))
+        .with_severity(Severity::Warning)
+        .in_function(index)
+    }));
+    CheckResult::from_findings(findings)
+}
+
+/// Warns about imported functions which are never called, exported or placed in the table.
+pub fn chk_unused_imports(ctx: &CheckContext) -> CheckResult {
+    let graph = CallGraph::build(ctx.module());
+    trace!("unused imports: functions={}", graph.len());
+    CheckResult::from_findings(
+        unused_in(&graph)
+            .into_iter()
+            .filter_map(|index| graph.import_name(index))
+            .map(|(namespace, name)| {
+                Finding::new(&format!(
+                    "{}.{} is imported but never called",
+                    namespace, name
+                ))
+                .with_severity(Severity::Warning)
+            })
+            .collect(),
+    )
+}
+
+/// Verifies that every metered block charges at least the cost of its instructions under the
+/// profile's opcode costs. Blocks whose charge is not a constant cannot be verified and are
+/// reported as warnings. Not part of the default checklist; add it with the ID "metering-costs".
+pub fn chk_metering_costs(ctx: &CheckContext) -> CheckResult {
+    let found = undercharged_blocks(ctx.module(), ctx.interface(), ctx.profile().opcode_costs());
+    trace!("metering costs: undercharged_blocks={}", found.len());
+    CheckResult::from_findings(
+        found
+            .iter()
+            .map(|block| {
+                let finding = match block.charged {
+                    Some(charged) => Finding::new(&format!(
+                        "block at instructions {}..{} charges {} gas but costs {}, {} too little",
+                        block.start,
+                        block.end,
+                        charged,
+                        block.expected,
+                        block.expected - charged
+                    )),
+                    None => Finding::new(&format!(
+                        "block at instructions {}..{} charges a non-constant amount of gas but \
+                         costs {}",
+                        block.start, block.end, block.expected
+                    ))
+                    .with_severity(Severity::Warning),
+                };
+                finding.in_function(block.function)
+            })
+            .collect(),
+    )
+}
+
+/// Verifies that every function the module defines updates a stack height counter which traps on
+/// overflow. Not part of the default checklist; add it with the ID "stack-limit".
+pub fn chk_stack_limit(ctx: &CheckContext) -> CheckResult {
+    let detected = detect_stack_limit(ctx.module());
+    trace!(
+        "stack limit: global={:?}",
+        detected.as_ref().map(|limit| limit.global)
+    );
+    match detected {
+        Some(limit) => CheckResult::from_findings(
+            limit
+                .uninstrumented
+                .iter()
+                .map(|&index| {
+                    Finding::new(&format!(
+                        "function {} does not update the stack height in global {}",
+                        index, limit.global
+                    ))
+                    .in_function(index)
+                })
+                .collect(),
+        ),
+        None if ctx.module().code_section().is_none() => CheckStatus::Good.into(),
+        None => CheckResult::new(
+            CheckStatus::Malformed,
+            vec![Finding::new("module does not limit its stack height")],
+        ),
+    }
+}
+
+/// Verifies that the bytecode is canonically encoded, so that byte-different encodings of the same
+/// module cannot produce different code hashes. Not part of the default checklist; add it with
+/// the ID "canonical-encoding".
+pub fn chk_canonical_encoding(ctx: &CheckContext) -> CheckResult {
+    trace!("canonical encoding: bytes={}", ctx.code().len());
+    match find_non_canonical(ctx.code()) {
+        Ok(found) => CheckResult::from_findings(
+            found
+                .iter()
+                .map(|nc| Finding::new(&nc.to_string()).at_offset(nc.offset))
+                .collect(),
+        ),
+        Err(e) => CheckResult::new(
+            CheckStatus::Malformed,
+            vec![Finding::new(&e.to_string()).at_offset(e.offset)],
+        ),
     }
 }
 
@@ -78,7 +335,7 @@ pub fn chk_func_signatures(module: &Module) -> CheckStatus {
 pub fn has_func_export(module: &Module, name: &str, sig: FunctionType) -> CheckStatus {
     match resolve_export_by_name(module, name) {
         Some((index, reference)) => if reference == Internal::Function(index)
-            && func_type_by_index(module, index as usize) == sig
+            && callable_func_type(module, index).as_ref() == Some(&sig)
         {
             CheckStatus::Good
         } else {
@@ -89,55 +346,201 @@ pub fn has_func_export(module: &Module, name: &str, sig: FunctionType) -> CheckS
 }
 
 /// Checks that the module only imports functions from the "ethereum" namespace.
-pub fn imports_only_eei_namespace(module: &Module) -> CheckStatus {
+pub fn imports_only_eei_namespace(module: &Module) -> CheckResult {
+    imports_only_permitted_namespaces(module, &Profile::default())
+}
+
+/// Checks that the module only imports from namespaces permitted by the given profile.
+pub fn imports_only_permitted_namespaces(module: &Module, profile: &Profile) -> CheckResult {
     let importlist = get_imports(module).unwrap();
 
-    for (module, _field) in importlist {
-        if module != "ethereum" {
-            return CheckStatus::Malformed;
+    CheckResult::from_findings(
+        importlist
+            .iter()
+            .filter(|(module, _field)| !profile.permits_namespace(module))
+            .map(|(module, field)| {
+                Finding::new(&format!(
+                    "{}.{} is imported from namespace \"{}\", which profile \"{}\" does not permit",
+                    module,
+                    field,
+                    module,
+                    profile.name()
+                ))
+            })
+            .collect(),
+    )
+}
+
+/// Describes each function imported from the "debug" namespace and the call sites using it. Will
+/// fail if the module doesn't have an import section.
+pub fn debug_imports(module: &Module, eei: &ImportInterfaceMap) -> Vec<Finding> {
+    let mut findings: Vec<Finding> = Vec::new();
+    let imports = module.import_section().unwrap();
+    let func_imports = imports
+        .entries()
+        .iter()
+        .filter(|entry| matches!(*entry.external(), External::Function(_)));
+
+    for (func_index, entry) in func_imports.enumerate() {
+        if entry.module() != DEBUG_NAMESPACE {
+            continue;
         }
+
+        let name = format!("{}.{}", entry.module(), entry.field());
+        let description = match eei.lookup_func(entry.module(), entry.field()) {
+            Some(sig) => format!("debugging function {}{}", name, func_type_to_string(&sig)),
+            None => format!("unknown debugging function {}", name),
+        };
+        let sites: Vec<String> = find_call_sites(module, func_index as u32)
+            .iter()
+            .map(|(caller, instr)| format!("function {} instruction {}", caller, instr))
+            .collect();
+
+        findings.push(
+            Finding::new(&if sites.is_empty() {
+                format!("{} is imported but never called", description)
+            } else {
+                format!("{} is called from {}", description, sites.join(", "))
+            })
+            .in_function(func_index as u32),
+        );
     }
-    CheckStatus::Good
+
+    findings
 }
 
-/// Checks the imported function signatures against the EEI import list. Will fail if the module
-/// doesn't have an import section.
-pub fn eei_check_func_sigs(module: &Module) -> CheckStatus {
-    let eei = ImportInterfaceMap::default();
+/// Describes each imported function deprecated by the given EEI import list, as warnings. Will
+/// fail if the module doesn't have an import section.
+pub fn deprecated_imports(module: &Module, eei: &ImportInterfaceMap) -> Vec<Finding> {
+    let imports = module.import_section().unwrap();
 
-    module
-        .import_section()
-        .unwrap()
+    imports
         .entries()
         .iter()
-        .map(|x| (x.field(), x.external()))
-        .map(|(name, binding)| {
-            (
-                eei.get_func(name),
-                match *binding {
-                    External::Function(idx) => {
-                        Some(imported_func_type_by_index(module, idx as usize))
-                    }
-                    _ => None,
-                },
+        .filter(|entry| matches!(*entry.external(), External::Function(_)))
+        .enumerate()
+        .filter_map(|(func_index, entry)| {
+            let func = eei.get_host_function(entry.module(), entry.field())?;
+            let deprecation = func.deprecation()?;
+
+            let mut message = format!(
+                "{}.{} is deprecated since EEI revision {}",
+                entry.module(),
+                entry.field(),
+                deprecation.since
+            );
+            if let Some(ref replacement) = deprecation.replacement {
+                message.push_str(&format!(", use {}.{} instead", entry.module(), replacement));
+            }
+            if let Some(ref note) = deprecation.note {
+                message.push_str(&format!(" ({})", note));
+            }
+
+            Some(
+                Finding::new(&message)
+                    .with_severity(Severity::Warning)
+                    .in_function(func_index as u32),
             )
         })
-        .map(|(correctsig, funcsig)| {
-            if correctsig != None
-                && funcsig != None
-                && funcsig.unwrap().clone() == correctsig.unwrap()
-            {
-                CheckStatus::Good
-            } else {
-                CheckStatus::Malformed
+        .collect()
+}
+
+/// Checks the imported function signatures against the given EEI import list, per namespace.
+/// Imports from namespaces the list does not describe are reported. Will fail if the module
+/// doesn't have an import section.
+pub fn eei_check_func_sigs(module: &Module, eei: &ImportInterfaceMap) -> CheckResult {
+    check_func_sigs(module, eei, None)
+}
+
+/// Checks the imported function signatures, skipping namespaces which the interface does not
+/// describe but the profile, if given, explicitly permits.
+fn check_func_sigs(
+    module: &Module,
+    eei: &ImportInterfaceMap,
+    profile: Option<&Profile>,
+) -> CheckResult {
+    let mut findings: Vec<Finding> = Vec::new();
+    let mut func_index: u32 = 0;
+    let imports = module.import_section().unwrap();
+
+    for entry in imports.entries() {
+        let funcsig = match *entry.external() {
+            External::Function(_) => callable_func_type(module, func_index),
+            _ => {
+                findings.push(Finding::new(&format!(
+                    "{}.{} is not a function import",
+                    entry.module(),
+                    entry.field()
+                )));
+                continue;
             }
-        })
-        .find(|x| {
-            if *x == CheckStatus::Malformed {
-                true
-            } else {
-                false
+        };
+        let funcsig = match funcsig {
+            Some(funcsig) => funcsig,
+            None => {
+                findings.push(
+                    Finding::new(&format!(
+                        "{}.{} refers to a type which does not exist",
+                        entry.module(),
+                        entry.field()
+                    ))
+                    .in_function(func_index),
+                );
+                func_index += 1;
+                continue;
             }
-        })
-        .unwrap_or(CheckStatus::Good)
+        };
+
+        let permitted_extension = !eei.has_namespace(entry.module())
+            && profile.is_some_and(|p| p.permits_namespace(entry.module()));
+        if !permitted_extension {
+            if let Some(mismatch) = import_mismatch(eei, entry.module(), entry.field(), &funcsig) {
+                findings.push(Finding::new(&mismatch).in_function(func_index));
+            }
+        }
+        func_index += 1;
+    }
+
+    CheckResult::from_findings(findings)
+}
+
+/// Describes how an imported function disagrees with the given EEI import list, if it does.
+pub fn import_mismatch(
+    eei: &ImportInterfaceMap,
+    namespace: &str,
+    field: &str,
+    funcsig: &FunctionType,
+) -> Option<String> {
+    if !eei.has_namespace(namespace) {
+        return Some(format!(
+            "{}.{} is imported from namespace \"{}\", which EEI revision {} does not describe",
+            namespace,
+            field,
+            namespace,
+            eei.revision_number()
+        ));
+    }
+
+    match eei.lookup_func(namespace, field) {
+        Some(ref correctsig) if correctsig == funcsig => None,
+        Some(correctsig) => Some(format!(
+            "{}.{} has signature {}, expected {}",
+            namespace,
+            field,
+            func_type_to_string(funcsig),
+            func_type_to_string(&correctsig)
+        )),
+        None => Some(match eei.removed_in(namespace, field) {
+            Some(rev) => format!(
+                "{}.{} was removed in EEI revision {}",
+                namespace, field, rev
+            ),
+            None => format!(
+                "{}.{} is not a function of EEI revision {}",
+                namespace,
+                field,
+                eei.revision_number()
+            ),
+        }),
+    }
 }
@@ -22,251 +22,376 @@
  * SOFTWARE.
  */
 
-use parity_wasm::elements::{FunctionType, ValueType};
+use gas::{GasCostError, GasCostTable};
+use parity_wasm::elements::FunctionType;
+use spec::{InterfaceSpec, SpecError};
 use std::collections::HashMap;
+use std::sync::OnceLock;
 
-static EEI_REV: i32 = 3;
+/// The latest EEI revision known to libeci, used by `ImportInterfaceMap::default`. The built-in
+/// spec describes the interface as this single revision.
+pub const EEI_REV: u32 = 3;
 
-/// Struct mapping an imported function name to its expected signature.
+/// The namespace from which the EEI host functions are imported.
+pub const EEI_NAMESPACE: &str = "ethereum";
+
+/// The namespace from which the ewasm debugging functions are imported.
+pub const DEBUG_NAMESPACE: &str = "debug";
+
+/// What a host function does with one of its parameters.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ParamSemantics {
+    /// A plain value, or a parameter whose meaning is not described.
+    Value,
+    /// A memory offset from which the given number of bytes is read.
+    InPtr(u32),
+    /// A memory offset to which the given number of bytes is written.
+    OutPtr(u32),
+    /// A memory offset from which as many bytes are read as the parameter at the given index says.
+    InPtrLen(usize),
+    /// A memory offset to which as many bytes are written as the parameter at the given index
+    /// says.
+    OutPtrLen(usize),
+    /// A number of bytes.
+    Length,
+    /// How many of the parameters from the first to the last given index are used, counting from
+    /// the first. The rest are ignored by the host.
+    Count(usize, usize),
+    /// An amount of gas.
+    Gas,
+}
+
+/// A host function's signature, along with the names and semantics of its parameters if known.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FunctionDecl {
+    pub signature: FunctionType,
+    /// Either empty, or one name per parameter.
+    pub param_names: Vec<String>,
+    /// Either empty, or one entry per parameter.
+    pub semantics: Vec<ParamSemantics>,
+}
+
+impl FunctionDecl {
+    /// Constructs a declaration with unnamed and undescribed parameters.
+    pub fn new(signature: FunctionType) -> Self {
+        FunctionDecl {
+            signature,
+            param_names: Vec::new(),
+            semantics: Vec::new(),
+        }
+    }
+}
+
+/// Marks a host function as superseded. Deprecated functions remain part of the interface.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Deprecation {
+    /// The EEI revision in which the function was deprecated.
+    pub since: u32,
+    /// The function of the same namespace which supersedes it, if any.
+    pub replacement: Option<String>,
+    pub note: Option<String>,
+}
+
+/// A change made to a host function in a given EEI revision.
+#[derive(Clone, Debug, PartialEq)]
+pub enum InterfaceChange {
+    /// The function was introduced with the given declaration.
+    Added(FunctionDecl),
+    /// The function's signature was changed.
+    Changed(FunctionDecl),
+    /// The function was removed.
+    Removed,
+    /// The function was deprecated.
+    Deprecated(Deprecation),
+}
+
+/// An imported host function's expected signature and the revisions that shaped it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HostFunction {
+    namespace: String,
+    name: String,
+    decl: FunctionDecl,
+    added_in: u32,
+    changed_in: Option<u32>,
+    deprecation: Option<Deprecation>,
+}
+
+impl HostFunction {
+    /// Returns the namespace the function is imported from.
+    pub fn namespace(&self) -> &str {
+        &self.namespace
+    }
+
+    /// Returns the name the function is imported by.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the expected signature of the function.
+    pub fn signature(&self) -> &FunctionType {
+        &self.decl.signature
+    }
+
+    /// Returns the name of a parameter, if the interface names it.
+    pub fn param_name(&self, index: usize) -> Option<&str> {
+        self.decl.param_names.get(index).map(|name| name.as_str())
+    }
+
+    /// Returns what the function does with a parameter. Parameters the interface does not
+    /// describe are plain values.
+    pub fn param_semantics(&self, index: usize) -> ParamSemantics {
+        self.decl
+            .semantics
+            .get(index)
+            .cloned()
+            .unwrap_or(ParamSemantics::Value)
+    }
+
+    /// Returns the EEI revision in which the function was introduced.
+    pub fn added_in(&self) -> u32 {
+        self.added_in
+    }
+
+    /// Returns the latest EEI revision in which the function's signature changed, if any.
+    pub fn changed_in(&self) -> Option<u32> {
+        self.changed_in
+    }
+
+    /// Returns the deprecation of the function, if it has been deprecated.
+    pub fn deprecation(&self) -> Option<&Deprecation> {
+        self.deprecation.as_ref()
+    }
+}
+
+/// Struct mapping an imported function's namespace and name to its expected signature.
+#[derive(Clone, Debug, PartialEq)]
 pub struct ImportInterfaceMap {
-    import_list: HashMap<String, FunctionType>,
+    revision: u32,
+    import_list: HashMap<(String, String), HostFunction>,
+    removed: HashMap<(String, String), u32>,
+    gas_costs: Option<GasCostTable>,
 }
 
 impl ImportInterfaceMap {
     pub fn new() -> Self {
         ImportInterfaceMap {
+            revision: 0,
             import_list: HashMap::new(),
+            removed: HashMap::new(),
+            gas_costs: None,
         }
     }
 
+    /// Constructs an ImportInterfaceMap containing the host functions of the given EEI revision.
+    /// Revisions newer than `EEI_REV` are treated as `EEI_REV`.
+    pub fn revision(rev: u32) -> Self {
+        InterfaceSpec::builtin().build(rev)
+    }
+
+    /// Constructs an ImportInterfaceMap from the latest revision described by an interface spec.
+    pub fn from_spec(text: &str) -> Result<Self, SpecError> {
+        let spec = InterfaceSpec::parse(text)?;
+        Ok(spec.build(spec.latest_revision()))
+    }
+
+    /// Applies a single change made to a function of the given namespace in the given revision.
+    pub fn apply(&mut self, rev: u32, namespace: &str, name: &str, change: InterfaceChange) {
+        let key = (namespace.to_string(), name.to_string());
+        match change {
+            InterfaceChange::Added(decl) => {
+                self.removed.remove(&key);
+                self.import_list.insert(
+                    key,
+                    HostFunction {
+                        namespace: namespace.to_string(),
+                        name: name.to_string(),
+                        decl,
+                        added_in: rev,
+                        changed_in: None,
+                        deprecation: None,
+                    },
+                );
+            }
+            InterfaceChange::Changed(decl) => {
+                if let Some(func) = self.import_list.get_mut(&key) {
+                    func.decl = decl;
+                    func.changed_in = Some(rev);
+                }
+            }
+            InterfaceChange::Deprecated(deprecation) => {
+                if let Some(func) = self.import_list.get_mut(&key) {
+                    func.deprecation = Some(deprecation);
+                }
+            }
+            InterfaceChange::Removed => {
+                if self.import_list.remove(&key).is_some() {
+                    self.removed.insert(key, rev);
+                }
+            }
+        }
+        self.revision = self.revision.max(rev);
+    }
+
+    /// Returns the EEI revision this map describes.
+    pub fn revision_number(&self) -> u32 {
+        self.revision
+    }
+
+    /// Sets the EEI revision this map describes.
+    pub fn set_revision_number(&mut self, rev: u32) {
+        self.revision = rev;
+    }
+
+    /// Returns the signature of the host function with the given name in the `ethereum`
+    /// namespace.
+    #[deprecated(since = "0.1.0", note = "use lookup_func, which takes the namespace")]
     pub fn get_func(&self, name: &str) -> Option<FunctionType> {
-        let result = self.import_list.get(&name.to_string());
+        self.lookup_func(EEI_NAMESPACE, name)
+    }
+
+    /// Returns the signature of the host function with the given namespace and name.
+    pub fn lookup_func(&self, namespace: &str, name: &str) -> Option<FunctionType> {
+        self.get_host_function(namespace, name)
+            .map(|func| func.signature().clone())
+    }
+
+    /// Returns the host function with the given namespace and name, along with its revision
+    /// information.
+    pub fn get_host_function(&self, namespace: &str, name: &str) -> Option<&HostFunction> {
+        self.import_list
+            .get(&(namespace.to_string(), name.to_string()))
+    }
+
+    /// Returns every host function of the map, ordered by namespace and name.
+    pub fn functions(&self) -> Vec<&HostFunction> {
+        let mut functions: Vec<&HostFunction> = self.import_list.values().collect();
+        functions.sort_by(|a, b| (&a.namespace, &a.name).cmp(&(&b.namespace, &b.name)));
+        functions
+    }
+
+    /// Returns the namespace, name and removal revision of every function removed before this
+    /// revision, ordered by namespace and name.
+    pub fn removed_functions(&self) -> Vec<(&str, &str, u32)> {
+        let mut removed: Vec<(&str, &str, u32)> = self
+            .removed
+            .iter()
+            .map(|((ns, name), rev)| (ns.as_str(), name.as_str(), *rev))
+            .collect();
+        removed.sort();
+        removed
+    }
+
+    /// Returns the revision in which a function was removed, if it existed in an earlier revision
+    /// but not in this one.
+    pub fn removed_in(&self, namespace: &str, name: &str) -> Option<u32> {
+        self.removed
+            .get(&(namespace.to_string(), name.to_string()))
+            .cloned()
+    }
+
+    /// Sets the gas cost table of the network the map is used for. Every priced function must be
+    /// part of the interface, now or in an earlier revision, and every length parameter the table
+    /// charges for must be a parameter of its function.
+    pub fn set_gas_costs(&mut self, costs: GasCostTable) -> Result<(), GasCostError> {
+        for (namespace, name) in costs.functions() {
+            let key = (namespace.to_string(), name.to_string());
+            let func = match self.import_list.get(&key) {
+                Some(func) => func,
+                None if self.removed.contains_key(&key) => continue,
+                None => {
+                    return Err(GasCostError::UnknownFunction(format!(
+                        "{}.{}",
+                        namespace, name
+                    )))
+                }
+            };
+            if let Some(ref param) = costs.cost(namespace, name).unwrap().length_param {
+                if !func.decl.param_names.contains(param) {
+                    return Err(GasCostError::UnknownLengthParam(
+                        format!("{}.{}", namespace, name),
+                        param.clone(),
+                    ));
+                }
+            }
+        }
+        self.gas_costs = Some(costs);
+        Ok(())
+    }
+
+    /// Returns the gas cost table of the network the map is used for, if any.
+    pub fn gas_costs(&self) -> Option<&GasCostTable> {
+        self.gas_costs.as_ref()
+    }
+
+    /// Returns the gas cost of a call to a host function with the given arguments, where unknown
+    /// arguments are None. Returns None if the function is not priced, or if the cost depends on
+    /// an unknown length.
+    pub fn call_cost(&self, namespace: &str, name: &str, args: &[Option<u64>]) -> Option<u64> {
+        let cost = self.gas_costs.as_ref()?.cost(namespace, name)?;
+
+        match cost.length_param {
+            Some(ref param) => {
+                let func = self.get_host_function(namespace, name)?;
+                let index = func.decl.param_names.iter().position(|n| n == param)?;
+                Some(cost.charge((*args.get(index)?)?))
+            }
+            None => Some(cost.base),
+        }
+    }
+
+    /// Determines whether the map describes any function, present or removed, of a namespace.
+    pub fn has_namespace(&self, namespace: &str) -> bool {
+        self.import_list
+            .keys()
+            .chain(self.removed.keys())
+            .any(|(ns, _)| ns == namespace)
+    }
+
+    /// Returns the namespaces described by the map, in alphabetical order.
+    pub fn namespaces(&self) -> Vec<&str> {
+        let mut namespaces: Vec<&str> = self
+            .import_list
+            .keys()
+            .chain(self.removed.keys())
+            .map(|(ns, _)| ns.as_str())
+            .collect();
+        namespaces.sort();
+        namespaces.dedup();
+        namespaces
+    }
 
-        match result {
-            Some(func) => Some(func.clone()),
-            None => None,
+    /// Adds the functions of another map, e.g. one describing a library namespace, replacing any
+    /// with the same namespace and name.
+    pub fn extend(&mut self, other: ImportInterfaceMap) {
+        for (key, func) in other.import_list {
+            self.removed.remove(&key);
+            self.import_list.insert(key, func);
+        }
+        for (key, rev) in other.removed {
+            if !self.import_list.contains_key(&key) {
+                self.removed.insert(key, rev);
+            }
         }
     }
 }
 
 impl Default for ImportInterfaceMap {
-    /// Constructs an ImportInterfaceMap containing the function signatures of the ewasm EEI.
+    /// Constructs an ImportInterfaceMap containing the function signatures of the ewasm EEI. The
+    /// map is built once and cloned.
     fn default() -> Self {
-        ImportInterfaceMap {
-            import_list: {
-                let imports: HashMap<String, FunctionType> = [
-                    (
-                        "useGas".to_string(),
-                        FunctionType::new(vec![ValueType::I64], None),
-                    ),
-                    (
-                        "getGasLeft".to_string(),
-                        FunctionType::new(Vec::new(), Some(ValueType::I64)),
-                    ),
-                    (
-                        "getAddress".to_string(),
-                        FunctionType::new(vec![ValueType::I32], None),
-                    ),
-                    (
-                        "getExternalBalance".to_string(),
-                        FunctionType::new(vec![ValueType::I32, ValueType::I32], None),
-                    ),
-                    (
-                        "getBlockHash".to_string(),
-                        FunctionType::new(
-                            vec![ValueType::I64, ValueType::I32],
-                            Some(ValueType::I32),
-                        ),
-                    ),
-                    (
-                        "call".to_string(),
-                        FunctionType::new(
-                            vec![
-                                ValueType::I64,
-                                ValueType::I32,
-                                ValueType::I32,
-                                ValueType::I32,
-                                ValueType::I32,
-                            ],
-                            Some(ValueType::I32),
-                        ),
-                    ),
-                    (
-                        "callCode".to_string(),
-                        FunctionType::new(
-                            vec![
-                                ValueType::I64,
-                                ValueType::I32,
-                                ValueType::I32,
-                                ValueType::I32,
-                                ValueType::I32,
-                            ],
-                            Some(ValueType::I32),
-                        ),
-                    ),
-                    (
-                        "callDelegate".to_string(),
-                        FunctionType::new(
-                            vec![
-                                ValueType::I64,
-                                ValueType::I32,
-                                ValueType::I32,
-                                ValueType::I32,
-                            ],
-                            Some(ValueType::I32),
-                        ),
-                    ),
-                    (
-                        "callStatic".to_string(),
-                        FunctionType::new(
-                            vec![
-                                ValueType::I64,
-                                ValueType::I32,
-                                ValueType::I32,
-                                ValueType::I32,
-                            ],
-                            Some(ValueType::I32),
-                        ),
-                    ),
-                    (
-                        "create".to_string(),
-                        FunctionType::new(
-                            vec![
-                                ValueType::I64,
-                                ValueType::I32,
-                                ValueType::I32,
-                                ValueType::I32,
-                                ValueType::I32,
-                            ],
-                            Some(ValueType::I32),
-                        ),
-                    ),
-                    (
-                        "callDataCopy".to_string(),
-                        FunctionType::new(
-                            vec![ValueType::I32, ValueType::I32, ValueType::I32],
-                            None,
-                        ),
-                    ),
-                    (
-                        "getCallDataSize".to_string(),
-                        FunctionType::new(Vec::new(), Some(ValueType::I32)),
-                    ),
-                    (
-                        "getCodeSize".to_string(),
-                        FunctionType::new(Vec::new(), Some(ValueType::I32)),
-                    ),
-                    (
-                        "externalCodeCopy".to_string(),
-                        FunctionType::new(
-                            vec![
-                                ValueType::I32,
-                                ValueType::I32,
-                                ValueType::I32,
-                                ValueType::I32,
-                            ],
-                            None,
-                        ),
-                    ),
-                    (
-                        "getExternalCodeSize".to_string(),
-                        FunctionType::new(vec![ValueType::I32], Some(ValueType::I32)),
-                    ),
-                    (
-                        "getCaller".to_string(),
-                        FunctionType::new(vec![ValueType::I32], None),
-                    ),
-                    (
-                        "getCallValue".to_string(),
-                        FunctionType::new(vec![ValueType::I32], None),
-                    ),
-                    (
-                        "getBlockDifficulty".to_string(),
-                        FunctionType::new(vec![ValueType::I32], None),
-                    ),
-                    (
-                        "getBlockCoinbase".to_string(),
-                        FunctionType::new(vec![ValueType::I32], None),
-                    ),
-                    (
-                        "getBlockNumber".to_string(),
-                        FunctionType::new(Vec::new(), Some(ValueType::I64)),
-                    ),
-                    (
-                        "getBlockGasLimit".to_string(),
-                        FunctionType::new(Vec::new(), Some(ValueType::I64)),
-                    ),
-                    (
-                        "getBlockTimestamp".to_string(),
-                        FunctionType::new(Vec::new(), Some(ValueType::I64)),
-                    ),
-                    (
-                        "getTxGasPrice".to_string(),
-                        FunctionType::new(vec![ValueType::I32], None),
-                    ),
-                    (
-                        "getTxOrigin".to_string(),
-                        FunctionType::new(vec![ValueType::I32], None),
-                    ),
-                    (
-                        "storageStore".to_string(),
-                        FunctionType::new(vec![ValueType::I32, ValueType::I32], None),
-                    ),
-                    (
-                        "storageLoad".to_string(),
-                        FunctionType::new(vec![ValueType::I32, ValueType::I32], None),
-                    ),
-                    (
-                        "log".to_string(),
-                        FunctionType::new(
-                            vec![
-                                ValueType::I32,
-                                ValueType::I32,
-                                ValueType::I32,
-                                ValueType::I32,
-                                ValueType::I32,
-                                ValueType::I32,
-                                ValueType::I32,
-                            ],
-                            None,
-                        ),
-                    ),
-                    (
-                        "getReturnDataSize".to_string(),
-                        FunctionType::new(Vec::new(), Some(ValueType::I32)),
-                    ),
-                    (
-                        "returnDataCopy".to_string(),
-                        FunctionType::new(
-                            vec![ValueType::I32, ValueType::I32, ValueType::I32],
-                            None,
-                        ),
-                    ),
-                    (
-                        "finish".to_string(),
-                        FunctionType::new(vec![ValueType::I32, ValueType::I32], None),
-                    ),
-                    (
-                        "revert".to_string(),
-                        FunctionType::new(vec![ValueType::I32, ValueType::I32], None),
-                    ),
-                    (
-                        "selfDestruct".to_string(),
-                        FunctionType::new(vec![ValueType::I32], None),
-                    ),
-                ].iter()
-                    .cloned()
-                    .collect();
-                imports
-            },
-        }
+        static LATEST: OnceLock<ImportInterfaceMap> = OnceLock::new();
+        LATEST
+            .get_or_init(|| ImportInterfaceMap::revision(EEI_REV))
+            .clone()
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use eei::ImportInterfaceMap;
+    use eei::{ImportInterfaceMap, ParamSemantics, DEBUG_NAMESPACE, EEI_NAMESPACE, EEI_REV};
+    use gas::{GasCostError, GasCostTable, HostCost};
     use parity_wasm::elements::{FunctionType, ValueType};
+    use spec::InterfaceSpec;
 
     #[test]
     fn empty_interface() {
@@ -275,6 +400,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(deprecated)]
     fn default_interface_has_methods() {
         let iface = ImportInterfaceMap::default();
         assert_eq!(
@@ -295,4 +421,208 @@ mod tests {
             )
         );
     }
+
+    #[test]
+    fn latest_revision_is_default() {
+        assert_eq!(
+            ImportInterfaceMap::revision(EEI_REV),
+            ImportInterfaceMap::default()
+        );
+        assert_eq!(ImportInterfaceMap::default().revision_number(), EEI_REV);
+        let iface = ImportInterfaceMap::default();
+        let eei_funcs = iface
+            .import_list
+            .keys()
+            .filter(|(ns, _)| ns == EEI_NAMESPACE)
+            .count();
+        assert_eq!(eei_funcs, 32);
+    }
+
+    // An interface which changes in each of three revisions.
+    const HISTORY: &str = "revision 1\n\
+                           namespace ethereum\n\
+                           return(i32, i32)\n\
+                           getBalance(i32, i32)\n\
+                           getBlockHash(i64, i32)\n\
+                           revision 2\n\
+                           namespace ethereum\n\
+                           finish(i32, i32)\n\
+                           remove return\n\
+                           revision 3\n\
+                           namespace ethereum\n\
+                           remove getBalance\n\
+                           change getBlockHash(i64, i32) -> i32";
+
+    #[test]
+    fn first_revision() {
+        let iface = InterfaceSpec::parse(HISTORY).unwrap().build(1);
+        assert!(iface.lookup_func("ethereum", "return").is_some());
+        assert!(iface.lookup_func("ethereum", "getBalance").is_some());
+        assert!(iface.lookup_func("ethereum", "finish").is_none());
+        assert_eq!(
+            iface.lookup_func("ethereum", "getBlockHash").unwrap(),
+            FunctionType::new(vec![ValueType::I64, ValueType::I32], None)
+        );
+    }
+
+    #[test]
+    fn revision_tags() {
+        let spec = InterfaceSpec::parse(HISTORY).unwrap();
+        let iface = spec.build(3);
+        let blockhash = iface.get_host_function("ethereum", "getBlockHash").unwrap();
+        assert_eq!(blockhash.added_in(), 1);
+        assert_eq!(blockhash.changed_in(), Some(3));
+        let finish = iface.get_host_function("ethereum", "finish").unwrap();
+        assert_eq!(finish.added_in(), 2);
+        assert_eq!(iface.removed_in("ethereum", "return"), Some(2));
+        assert_eq!(iface.removed_in("ethereum", "getBalance"), Some(3));
+        assert_eq!(iface.removed_in("ethereum", "finish"), None);
+        assert_eq!(spec.build(2).removed_in("ethereum", "getBalance"), None);
+    }
+
+    #[test]
+    fn builtin_revision() {
+        let iface = ImportInterfaceMap::default();
+        let blockhash = iface.get_host_function("ethereum", "getBlockHash").unwrap();
+        assert_eq!(blockhash.added_in(), EEI_REV);
+        assert_eq!(blockhash.changed_in(), None);
+        assert!(iface.removed_functions().is_empty());
+        assert!(ImportInterfaceMap::revision(EEI_REV - 1)
+            .import_list
+            .is_empty());
+    }
+
+    #[test]
+    fn revision_zero_is_empty() {
+        assert!(ImportInterfaceMap::revision(0).import_list.is_empty());
+    }
+
+    #[test]
+    fn interface_from_spec() {
+        let iface = ImportInterfaceMap::from_spec(
+            "revision 2\nnamespace env\nprint(offset: i32, length: i32)",
+        )
+        .unwrap();
+        assert_eq!(iface.revision_number(), 2);
+        let print = iface.get_host_function("env", "print").unwrap();
+        assert_eq!(print.namespace(), "env");
+        assert_eq!(print.param_name(1), Some("length"));
+        assert!(ImportInterfaceMap::from_spec("print()").is_err());
+    }
+
+    #[test]
+    fn functions_are_keyed_by_namespace() {
+        let iface = ImportInterfaceMap::default();
+        assert_eq!(iface.namespaces(), vec![DEBUG_NAMESPACE, EEI_NAMESPACE]);
+        assert_eq!(
+            iface.lookup_func(DEBUG_NAMESPACE, "print64").unwrap(),
+            FunctionType::new(vec![ValueType::I64], None)
+        );
+        assert!(iface.lookup_func(EEI_NAMESPACE, "print64").is_none());
+        assert!(iface.lookup_func(DEBUG_NAMESPACE, "useGas").is_none());
+        assert!(iface.has_namespace(DEBUG_NAMESPACE));
+        assert!(!iface.has_namespace("env"));
+    }
+
+    #[test]
+    fn extend_with_library_namespace() {
+        let mut iface = ImportInterfaceMap::default();
+        iface.extend(
+            ImportInterfaceMap::from_spec("revision 1\nnamespace keccak\nhash(i32, i32, i32)")
+                .unwrap(),
+        );
+        assert!(iface.has_namespace("keccak"));
+        assert!(iface.lookup_func("keccak", "hash").is_some());
+        assert!(iface.lookup_func(EEI_NAMESPACE, "useGas").is_some());
+        assert_eq!(iface.revision_number(), EEI_REV);
+    }
+
+    #[test]
+    fn parameter_semantics() {
+        let iface = ImportInterfaceMap::default();
+        let get_address = iface
+            .get_host_function(EEI_NAMESPACE, "getAddress")
+            .unwrap();
+        assert_eq!(get_address.param_semantics(0), ParamSemantics::OutPtr(20));
+        let storage_store = iface
+            .get_host_function(EEI_NAMESPACE, "storageStore")
+            .unwrap();
+        assert_eq!(storage_store.param_semantics(0), ParamSemantics::InPtr(32));
+        assert_eq!(storage_store.param_semantics(1), ParamSemantics::InPtr(32));
+        let call = iface.get_host_function(EEI_NAMESPACE, "call").unwrap();
+        assert_eq!(call.param_semantics(0), ParamSemantics::Gas);
+        assert_eq!(call.param_semantics(3), ParamSemantics::InPtrLen(4));
+        assert_eq!(call.param_semantics(4), ParamSemantics::Length);
+        let block_hash = iface
+            .get_host_function(EEI_NAMESPACE, "getBlockHash")
+            .unwrap();
+        assert_eq!(block_hash.param_semantics(0), ParamSemantics::Value);
+        assert_eq!(block_hash.param_semantics(1), ParamSemantics::OutPtr(32));
+        let log = iface.get_host_function(EEI_NAMESPACE, "log").unwrap();
+        assert_eq!(log.param_semantics(2), ParamSemantics::Count(3, 6));
+    }
+
+    #[test]
+    fn gas_costs() {
+        let mut iface = ImportInterfaceMap::default();
+        assert!(iface.gas_costs().is_none());
+        assert_eq!(iface.call_cost(EEI_NAMESPACE, "storageLoad", &[]), None);
+
+        iface
+            .set_gas_costs(GasCostTable::builtin("example").unwrap())
+            .unwrap();
+        assert_eq!(iface.gas_costs().unwrap().network(), "example");
+        assert_eq!(
+            iface.call_cost(EEI_NAMESPACE, "storageLoad", &[None, None]),
+            Some(200)
+        );
+        let copy = [None, Some(0), Some(64)];
+        assert_eq!(
+            iface.call_cost(EEI_NAMESPACE, "callDataCopy", &copy),
+            Some(3 + 2 * 3)
+        );
+        assert_eq!(
+            iface.call_cost(EEI_NAMESPACE, "callDataCopy", &[None, None, None]),
+            None
+        );
+        assert_eq!(iface.call_cost(DEBUG_NAMESPACE, "print32", &[None]), None);
+    }
+
+    #[test]
+    fn gas_costs_against_interface() {
+        // getBalance is removed in revision 3 of the test interface, but may still be priced.
+        let mut iface = InterfaceSpec::parse(HISTORY).unwrap().build(3);
+        let mut table = GasCostTable::new("test");
+        table.set_cost(EEI_NAMESPACE, "getBalance", HostCost::default());
+        assert!(iface.set_gas_costs(table.clone()).is_ok());
+
+        table.set_cost(EEI_NAMESPACE, "getBalanse", HostCost::default());
+        assert_eq!(
+            iface.set_gas_costs(table),
+            Err(GasCostError::UnknownFunction(
+                "ethereum.getBalanse".to_string()
+            ))
+        );
+
+        let mut iface = ImportInterfaceMap::default();
+        iface.set_gas_costs(GasCostTable::new("test")).unwrap();
+        let mut table = GasCostTable::new("test");
+        let copy = HostCost {
+            per_byte: 1,
+            length_param: Some("size".to_string()),
+            ..HostCost::default()
+        };
+        table.set_cost(EEI_NAMESPACE, "callDataCopy", copy);
+        assert_eq!(
+            iface.set_gas_costs(table),
+            Err(GasCostError::UnknownLengthParam(
+                "ethereum.callDataCopy".to_string(),
+                "size".to_string()
+            ))
+        );
+        // A rejected table leaves the previous one in place.
+        let costs = iface.gas_costs().unwrap();
+        assert_eq!(costs.network(), "test");
+        assert!(costs.cost(EEI_NAMESPACE, "callDataCopy").is_none());
+    }
 }
@@ -0,0 +1,299 @@
+/*
+ * libeci: Ethereum WebAssembly ABI compliance library
+ *
+ * Copyright (c) 2018 Jake Lang
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+use eei::{HostFunction, ImportInterfaceMap, ParamSemantics};
+use pwasm::value_type_name;
+
+/// Describes what a host function does with a parameter, in the notation of interface specs,
+/// e.g. "out[20]" or "in[dataLength]".
+pub fn semantics_name(func: &HostFunction, index: usize) -> String {
+    let len_name = |len: usize| match func.param_name(len) {
+        Some(name) => name.to_string(),
+        None => format!("arg{}", len),
+    };
+
+    match func.param_semantics(index) {
+        ParamSemantics::Value => "value".to_string(),
+        ParamSemantics::InPtr(len) => format!("in[{}]", len),
+        ParamSemantics::OutPtr(len) => format!("out[{}]", len),
+        ParamSemantics::InPtrLen(len) => format!("in[{}]", len_name(len)),
+        ParamSemantics::OutPtrLen(len) => format!("out[{}]", len_name(len)),
+        ParamSemantics::Length => "length".to_string(),
+        ParamSemantics::Gas => "gas".to_string(),
+        ParamSemantics::Count(first, last) => {
+            format!("count[{}..{}]", len_name(first), len_name(last))
+        }
+    }
+}
+
+/// Quotes and escapes a string as a JSON string literal.
+fn json_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Formats an optional string as a JSON string literal or null.
+fn json_opt_string(s: Option<&str>) -> String {
+    s.map(json_string).unwrap_or_else(|| "null".to_string())
+}
+
+/// Serializes the complete host interface of a map as JSON: the revision, every function with
+/// its parameters, result, revision information, deprecation and gas cost, and every function
+/// removed before the revision.
+pub fn interface_to_json(eei: &ImportInterfaceMap) -> String {
+    let functions: Vec<String> = eei
+        .functions()
+        .iter()
+        .map(|func| {
+            let sig = func.signature();
+            let params: Vec<String> = sig
+                .params()
+                .iter()
+                .enumerate()
+                .map(|(i, ty)| {
+                    format!(
+                        "{{\"name\": {}, \"type\": \"{}\", \"semantics\": \"{}\"}}",
+                        json_opt_string(func.param_name(i)),
+                        value_type_name(*ty),
+                        semantics_name(func, i)
+                    )
+                })
+                .collect();
+            let deprecation = match func.deprecation() {
+                Some(d) => format!(
+                    "{{\"since\": {}, \"replacement\": {}, \"note\": {}}}",
+                    d.since,
+                    json_opt_string(d.replacement.as_deref()),
+                    json_opt_string(d.note.as_deref())
+                ),
+                None => "null".to_string(),
+            };
+            let cost = match eei
+                .gas_costs()
+                .and_then(|table| table.cost(func.namespace(), func.name()))
+            {
+                Some(c) => format!(
+                    "{{\"base\": {}, \"per_byte\": {}, \"per_word\": {}, \"length\": {}}}",
+                    c.base,
+                    c.per_byte,
+                    c.per_word,
+                    json_opt_string(c.length_param.as_deref())
+                ),
+                None => "null".to_string(),
+            };
+
+            format!(
+                "    {{\"namespace\": {}, \"name\": {}, \"params\": [{}], \"result\": {}, \"added_in\": {}, \"changed_in\": {}, \"deprecated\": {}, \"gas\": {}}}",
+                json_string(func.namespace()),
+                json_string(func.name()),
+                params.join(", "),
+                json_opt_string(sig.return_type().map(value_type_name)),
+                func.added_in(),
+                func.changed_in()
+                    .map(|rev| rev.to_string())
+                    .unwrap_or_else(|| "null".to_string()),
+                deprecation,
+                cost
+            )
+        })
+        .collect();
+    let removed: Vec<String> = eei
+        .removed_functions()
+        .iter()
+        .map(|(ns, name, rev)| {
+            format!(
+                "    {{\"namespace\": {}, \"name\": {}, \"removed_in\": {}}}",
+                json_string(ns),
+                json_string(name),
+                rev
+            )
+        })
+        .collect();
+
+    format!(
+        "{{\n  \"revision\": {},\n  \"network\": {},\n  \"functions\": [\n{}\n  ],\n  \"removed\": [\n{}\n  ]\n}}\n",
+        eei.revision_number(),
+        json_opt_string(eei.gas_costs().map(|table| table.network())),
+        functions.join(",\n"),
+        removed.join(",\n")
+    )
+}
+
+/// Escapes the characters of a string which would break a Markdown table cell.
+fn markdown_cell(s: &str) -> String {
+    s.replace('|', "\\|").replace('\n', " ")
+}
+
+/// Renders the host interface of a map as a Markdown reference table.
+pub fn interface_to_markdown(eei: &ImportInterfaceMap) -> String {
+    let mut out = format!(
+        "# Host interface, EEI revision {}\n\n| Namespace | Function | Parameters | Result | Revision | Notes |\n| --- | --- | --- | --- | --- | --- |\n",
+        eei.revision_number()
+    );
+
+    for func in eei.functions() {
+        let sig = func.signature();
+        let params: Vec<String> = sig
+            .params()
+            .iter()
+            .enumerate()
+            .map(|(i, ty)| {
+                let param = match func.param_name(i) {
+                    Some(name) => format!("`{}: {}`", name, value_type_name(*ty)),
+                    None => format!("`{}`", value_type_name(*ty)),
+                };
+                match func.param_semantics(i) {
+                    ParamSemantics::Value => param,
+                    _ => format!("{} {}", param, semantics_name(func, i)),
+                }
+            })
+            .collect();
+        let revision = match func.changed_in() {
+            Some(rev) => format!("{} (changed in {})", func.added_in(), rev),
+            None => func.added_in().to_string(),
+        };
+        let mut notes: Vec<String> = Vec::new();
+        if let Some(d) = func.deprecation() {
+            let mut note = format!("Deprecated in {}", d.since);
+            if let Some(ref replacement) = d.replacement {
+                note.push_str(&format!(", use `{}`", replacement));
+            }
+            if let Some(ref reason) = d.note {
+                note.push_str(&format!(": {}", reason));
+            }
+            notes.push(note);
+        }
+        if let Some(cost) = eei
+            .gas_costs()
+            .and_then(|table| table.cost(func.namespace(), func.name()))
+        {
+            let mut gas = format!("Gas: {}", cost.base);
+            if cost.per_byte != 0 {
+                gas.push_str(&format!(" + {} per byte", cost.per_byte));
+            }
+            if cost.per_word != 0 {
+                gas.push_str(&format!(" + {} per word", cost.per_word));
+            }
+            notes.push(gas);
+        }
+
+        out.push_str(&format!(
+            "| {} | {} | {} | {} | {} | {} |\n",
+            markdown_cell(func.namespace()),
+            markdown_cell(func.name()),
+            markdown_cell(&params.join(", ")),
+            sig.return_type().map(value_type_name).unwrap_or(""),
+            revision,
+            markdown_cell(&notes.join(". "))
+        ));
+    }
+
+    let removed = eei.removed_functions();
+    if !removed.is_empty() {
+        out.push_str("\n## Removed functions\n\n| Namespace | Function | Removed in |\n| --- | --- | --- |\n");
+        for (ns, name, rev) in removed {
+            out.push_str(&format!(
+                "| {} | {} | {} |\n",
+                markdown_cell(ns),
+                markdown_cell(name),
+                rev
+            ));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use export::*;
+    use gas::GasCostTable;
+
+    #[test]
+    fn json() {
+        let mut eei = ImportInterfaceMap::revision(3);
+        let json = interface_to_json(&eei);
+        assert!(json.starts_with("{\n  \"revision\": 3,\n  \"network\": null,\n"));
+        assert!(json.contains(
+            "{\"namespace\": \"ethereum\", \"name\": \"getAddress\", \"params\": [{\"name\": \"resultOffset\", \"type\": \"i32\", \"semantics\": \"out[20]\"}], \"result\": null, \"added_in\": 3, \"changed_in\": null, \"deprecated\": null, \"gas\": null}"
+        ));
+        assert!(json.contains("\"name\": \"getBlockHash\""));
+        assert_eq!(json.matches('{').count(), json.matches('}').count());
+
+        eei.set_gas_costs(GasCostTable::builtin("example").unwrap())
+            .unwrap();
+        let json = interface_to_json(&eei);
+        assert!(json.contains("\"network\": \"example\""));
+        assert!(json.contains(
+            "\"gas\": {\"base\": 3, \"per_byte\": 0, \"per_word\": 3, \"length\": \"length\"}"
+        ));
+    }
+
+    #[test]
+    fn history() {
+        let eei = ImportInterfaceMap::from_spec(
+            "revision 1\nnamespace env\nfoo()\nbar()\nbaz()\n\
+             revision 2\nnamespace env\nchange foo() -> i32\nremove baz\n\
+             deprecate foo by bar: bar is faster",
+        )
+        .unwrap();
+        let json = interface_to_json(&eei);
+        assert!(json.contains("\"result\": \"i32\", \"added_in\": 1, \"changed_in\": 2"));
+        assert!(json.contains(
+            "\"deprecated\": {\"since\": 2, \"replacement\": \"bar\", \"note\": \"bar is faster\"}"
+        ));
+        assert!(json.contains("{\"namespace\": \"env\", \"name\": \"baz\", \"removed_in\": 2}"));
+
+        let md = interface_to_markdown(&eei);
+        assert!(md.contains("| env | foo |  | i32 | 1 (changed in 2) |"));
+        assert!(md.contains("Deprecated in 2, use `bar`: bar is faster"));
+        assert!(md.contains("\n## Removed functions\n"));
+        assert!(md.contains("| env | baz | 2 |\n"));
+    }
+
+    #[test]
+    fn json_escapes() {
+        assert_eq!(json_string("a\"b\\c\n\u{1}"), "\"a\\\"b\\\\c\\n\\u0001\"");
+    }
+
+    #[test]
+    fn markdown() {
+        let md = interface_to_markdown(&ImportInterfaceMap::revision(3));
+        assert!(md.starts_with("# Host interface, EEI revision 3\n\n| Namespace |"));
+        assert!(md.contains(
+            "| ethereum | callDataCopy | `resultOffset: i32` out[length], `dataOffset: i32`, `length: i32` length |  | 3 |  |\n"
+        ));
+        assert!(md.contains("| ethereum | getBlockHash | `number: i64`, `resultOffset: i32` out[32] | i32 | 3 |  |\n"));
+        assert!(!md.contains("\n## Removed functions\n"));
+    }
+}
@@ -0,0 +1,306 @@
+/*
+ * libeci: Ethereum WebAssembly ABI compliance library
+ *
+ * Copyright (c) 2018 Jake Lang
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+use spec::{is_identifier, SpecError, SpecErrorKind};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::OnceLock;
+
+/// An example gas cost table shipped with libeci. Its costs are illustrative and do not price any
+/// network.
+pub const EXAMPLE_GAS: &str = include_str!("../spec/gas-example.txt");
+
+/// The gas cost model of a single host function.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct HostCost {
+    /// Charged on every call.
+    pub base: u64,
+    /// Charged per byte of the length parameter.
+    pub per_byte: u64,
+    /// Charged per 32-byte word of the length parameter, rounding up.
+    pub per_word: u64,
+    /// The name of the length parameter the per-byte and per-word components apply to.
+    pub length_param: Option<String>,
+}
+
+impl HostCost {
+    /// Returns the cost of a call with the given value of the length parameter.
+    pub fn charge(&self, length: u64) -> u64 {
+        self.base
+            .saturating_add(self.per_byte.saturating_mul(length))
+            .saturating_add(self.per_word.saturating_mul(length.div_ceil(32)))
+    }
+}
+
+/// A gas cost table entry which the interface it is used with does not describe.
+#[derive(Clone, Debug, PartialEq)]
+pub enum GasCostError {
+    /// The table prices a function the interface has never had.
+    UnknownFunction(String),
+    /// The table charges for the length parameter of a function which has no such parameter.
+    UnknownLengthParam(String, String),
+}
+
+impl fmt::Display for GasCostError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            GasCostError::UnknownFunction(ref name) => {
+                write!(f, "gas cost of unknown function {}", name)
+            }
+            GasCostError::UnknownLengthParam(ref name, ref param) => write!(
+                f,
+                "gas cost of {} refers to unknown length parameter \"{}\"",
+                name, param
+            ),
+        }
+    }
+}
+
+/// Host function gas costs of a network, keyed by namespace and name.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GasCostTable {
+    network: String,
+    costs: HashMap<(String, String), HostCost>,
+}
+
+impl GasCostTable {
+    /// Constructs an empty table for the given network.
+    pub fn new(network: &str) -> Self {
+        GasCostTable {
+            network: network.to_string(),
+            costs: HashMap::new(),
+        }
+    }
+
+    /// Parses a gas cost table, which begins with the name of its network:
+    ///
+    /// ```text
+    /// network example
+    /// namespace ethereum
+    /// storageLoad base 200
+    /// callDataCopy base 3 word 3 length length
+    /// ```
+    pub fn parse(text: &str) -> Result<Self, SpecError> {
+        let mut table: Option<GasCostTable> = None;
+        let mut namespace: Option<String> = None;
+
+        for (i, raw) in text.lines().enumerate() {
+            let line = i + 1;
+            let error = |kind| Err(SpecError { line, kind });
+            let syntax = |msg: String| {
+                Err(SpecError {
+                    line,
+                    kind: SpecErrorKind::Syntax(msg),
+                })
+            };
+            let words: Vec<&str> = raw.split('#').next().unwrap().split_whitespace().collect();
+
+            match words.as_slice() {
+                [] => continue,
+                ["network", network] => {
+                    if table.is_some() {
+                        return syntax("a table describes only one network".to_string());
+                    }
+                    table = Some(GasCostTable::new(network));
+                    continue;
+                }
+                ["namespace", ns] if is_identifier(ns) => {
+                    namespace = Some(ns.to_string());
+                    continue;
+                }
+                ["network", ..] | ["namespace", ..] => {
+                    return syntax(format!("invalid declaration \"{}\"", raw.trim()))
+                }
+                _ => (),
+            }
+
+            let table = match table {
+                Some(ref mut table) => table,
+                None => return syntax("expected a network declaration".to_string()),
+            };
+            let ns = match namespace {
+                Some(ref ns) => ns,
+                None => return error(SpecErrorKind::MissingNamespace),
+            };
+            let name = words[0];
+            if !is_identifier(name) || words.len().is_multiple_of(2) {
+                return syntax(format!(
+                    "expected \"name key value ...\" in \"{}\"",
+                    raw.trim()
+                ));
+            }
+
+            let mut cost = HostCost::default();
+            for pair in words[1..].chunks(2) {
+                let value = || {
+                    pair[1].parse::<u64>().map_err(|_| SpecError {
+                        line,
+                        kind: SpecErrorKind::Syntax(format!(
+                            "invalid cost \"{}\" of {}",
+                            pair[1], name
+                        )),
+                    })
+                };
+                match pair[0] {
+                    "base" => cost.base = value()?,
+                    "byte" => cost.per_byte = value()?,
+                    "word" => cost.per_word = value()?,
+                    "length" if is_identifier(pair[1]) => {
+                        cost.length_param = Some(pair[1].to_string())
+                    }
+                    key => {
+                        return syntax(format!("invalid cost component \"{} {}\"", key, pair[1]))
+                    }
+                }
+            }
+            if (cost.per_byte != 0 || cost.per_word != 0) && cost.length_param.is_none() {
+                return syntax(format!(
+                    "{} has per-byte or per-word costs but no length",
+                    name
+                ));
+            }
+
+            let key = (ns.clone(), name.to_string());
+            if table.costs.contains_key(&key) {
+                return error(SpecErrorKind::Duplicate(format!("{}.{}", ns, name)));
+            }
+            table.costs.insert(key, cost);
+        }
+
+        match table {
+            Some(table) => Ok(table),
+            None => Err(SpecError {
+                line: text.lines().count(),
+                kind: SpecErrorKind::Syntax("expected a network declaration".to_string()),
+            }),
+        }
+    }
+
+    /// Returns a gas cost table shipped with libeci, if there is one by that name. The only one
+    /// is "example", which is parsed only once.
+    pub fn builtin(network: &str) -> Option<Self> {
+        static EXAMPLE: OnceLock<GasCostTable> = OnceLock::new();
+        match network {
+            "example" => Some(
+                EXAMPLE
+                    .get_or_init(|| {
+                        GasCostTable::parse(EXAMPLE_GAS).expect("built-in gas table is valid")
+                    })
+                    .clone(),
+            ),
+            _ => None,
+        }
+    }
+
+    /// Returns the name of the network the table describes.
+    pub fn network(&self) -> &str {
+        &self.network
+    }
+
+    /// Sets the cost of a host function.
+    pub fn set_cost(&mut self, namespace: &str, name: &str, cost: HostCost) {
+        self.costs
+            .insert((namespace.to_string(), name.to_string()), cost);
+    }
+
+    /// Returns the cost of a host function, if the table prices it.
+    pub fn cost(&self, namespace: &str, name: &str) -> Option<&HostCost> {
+        self.costs.get(&(namespace.to_string(), name.to_string()))
+    }
+
+    /// Returns the namespace and name of every priced function, in alphabetical order.
+    pub fn functions(&self) -> Vec<(&str, &str)> {
+        let mut functions: Vec<(&str, &str)> = self
+            .costs
+            .keys()
+            .map(|(ns, name)| (ns.as_str(), name.as_str()))
+            .collect();
+        functions.sort();
+        functions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use gas::*;
+
+    #[test]
+    fn builtin_table() {
+        let table = GasCostTable::builtin("example").unwrap();
+        assert_eq!(table.network(), "example");
+        assert_eq!(table.cost("ethereum", "storageStore").unwrap().base, 20000);
+        let copy = table.cost("ethereum", "callDataCopy").unwrap();
+        assert_eq!(copy.length_param, Some("length".to_string()));
+        assert_eq!(copy.charge(0), 3);
+        assert_eq!(copy.charge(33), 3 + 2 * 3);
+        assert!(table.cost("debug", "print32").is_none());
+        assert!(GasCostTable::builtin("ewasm-testnet").is_none());
+    }
+
+    #[test]
+    fn parse_table() {
+        let table = GasCostTable::parse(
+            "network test\nnamespace env\nhash base 30 byte 1 word 6 length len # sha3\n",
+        )
+        .unwrap();
+        let hash = table.cost("env", "hash").unwrap();
+        assert_eq!(
+            *hash,
+            HostCost {
+                base: 30,
+                per_byte: 1,
+                per_word: 6,
+                length_param: Some("len".to_string()),
+            }
+        );
+        assert_eq!(hash.charge(64), 30 + 64 + 2 * 6);
+    }
+
+    #[test]
+    fn invalid_tables() {
+        let kind = |text: &str| GasCostTable::parse(text).unwrap_err().kind;
+        assert_eq!(
+            kind("network a\nnamespace env\nfoo base 1\nfoo base 2"),
+            SpecErrorKind::Duplicate("env.foo".to_string())
+        );
+        assert_eq!(
+            kind("network a\nfoo base 1"),
+            SpecErrorKind::MissingNamespace
+        );
+        for text in &[
+            "namespace env\nfoo base 1",
+            "network a\nnamespace env\nfoo base",
+            "network a\nnamespace env\nfoo base x",
+            "network a\nnamespace env\nfoo word 3",
+            "network a\nnamespace env\nfoo fee 3",
+            "network a\nnetwork b",
+            "",
+        ] {
+            match kind(text) {
+                SpecErrorKind::Syntax(_) => (),
+                other => panic!("unexpected error {:?} for {:?}", other, text),
+            }
+        }
+    }
+}
@@ -22,10 +22,30 @@
  * SOFTWARE.
  */
 
+#[macro_use]
+extern crate log;
 extern crate parity_wasm;
 
+pub mod batch;
+pub mod binary;
+pub mod bindings;
+pub mod bounds;
+pub mod callgraph;
+pub mod canonical;
+pub mod capabilities;
 pub mod checker;
 pub mod checklist;
 pub mod eci_std;
 pub mod eei;
+pub mod export;
+pub mod gas;
+pub mod limits;
+pub mod metering;
+pub mod profile;
+pub mod prune;
 pub mod pwasm;
+pub mod revision;
+pub mod spec;
+pub mod stack_height;
+pub mod structure;
+pub mod transform;
@@ -0,0 +1,447 @@
+/*
+ * libeci: Ethereum WebAssembly ABI compliance library
+ *
+ * Copyright (c) 2018 Jake Lang
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+use binary::{immediates, opens_block, read_leb, Immediate, END};
+use checker::ModuleError;
+use parity_wasm::elements::Instruction;
+use std::fmt;
+use std::time::Duration;
+use structure::{SectionHeader, StructureError, StructureErrorKind};
+
+/// Bounds on the work libeci will perform for a single module. Every limit is disabled by
+/// default.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ResourceLimits {
+    /// Maximum length of the bytecode, enforced before deserialization.
+    pub max_input_bytes: Option<usize>,
+    /// Maximum number of functions, both imported and defined.
+    pub max_functions: Option<usize>,
+    /// Maximum number of instructions in a single function body.
+    pub max_body_size: Option<usize>,
+    /// Maximum nesting depth of blocks, loops and ifs in a function body.
+    pub max_nesting_depth: Option<usize>,
+    /// Maximum number of instructions across all function bodies.
+    pub max_instructions: Option<usize>,
+    /// Maximum wall-clock time a single check may take before its result is discarded. The time
+    /// is measured when the check returns, so a slow check runs to completion.
+    pub check_time_budget: Option<Duration>,
+}
+
+/// Identifies a resource limit.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Limit {
+    InputBytes,
+    Functions,
+    BodySize,
+    NestingDepth,
+    Instructions,
+}
+
+/// Describes a resource limit which a module has exceeded.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LimitExceeded {
+    pub limit: Limit,
+    pub max: usize,
+    pub actual: usize,
+    /// Callable index of the offending function, for per-function limits.
+    pub function: Option<u32>,
+}
+
+impl Limit {
+    /// Returns a human-readable name for the limit.
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            Limit::InputBytes => "input bytes",
+            Limit::Functions => "functions",
+            Limit::BodySize => "function body size",
+            Limit::NestingDepth => "nesting depth",
+            Limit::Instructions => "total instructions",
+        }
+    }
+}
+
+impl fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} limit exceeded: {} > {}",
+            self.limit.as_str(),
+            self.actual,
+            self.max
+        )?;
+        if let Some(index) = self.function {
+            write!(f, " in function {}", index)?;
+        }
+        Ok(())
+    }
+}
+
+impl ResourceLimits {
+    /// Constructs a set of limits with every limit disabled.
+    pub fn new() -> Self {
+        ResourceLimits::default()
+    }
+
+    /// Checks the raw bytecode against the limits which can be enforced before deserialization.
+    pub fn check_input(&self, code: &[u8]) -> Result<(), LimitExceeded> {
+        exceeds(Limit::InputBytes, self.max_input_bytes, code.len(), None)
+    }
+
+    /// Checks the raw bytecode against the function, body size, nesting depth and instruction
+    /// limits by walking the import, function and code sections found by the structure pre-scan,
+    /// so that oversized modules are rejected before they are deserialized. A section whose
+    /// contents cannot be walked is reported as malformed.
+    pub fn check_sections(
+        &self,
+        code: &[u8],
+        sections: &[SectionHeader],
+    ) -> Result<(), ModuleError> {
+        let mut imported: usize = 0;
+        let mut defined: usize = 0;
+        for header in sections {
+            match header.id {
+                2 => imported = walk(code, header, |reader| reader.function_imports())?,
+                3 => defined = walk(code, header, |reader| reader.varuint32())? as usize,
+                _ => (),
+            }
+        }
+        exceeds(
+            Limit::Functions,
+            self.max_functions,
+            imported + defined,
+            None,
+        )
+        .map_err(ModuleError::ResourceLimitExceeded)?;
+
+        let walk_bodies = self.max_body_size.is_some()
+            || self.max_nesting_depth.is_some()
+            || self.max_instructions.is_some();
+        let bodies = match sections.iter().find(|header| header.id == 10) {
+            Some(header) if walk_bodies => walk(code, header, |reader| {
+                (0..reader.varuint32()?)
+                    .map(|_| reader.body())
+                    .collect::<Option<Vec<_>>>()
+            })?,
+            _ => return Ok(()),
+        };
+        self.check_bodies(imported, &bodies)
+            .map_err(ModuleError::ResourceLimitExceeded)
+    }
+
+    /// Checks the instruction count and nesting depth measured for each function body.
+    fn check_bodies(
+        &self,
+        imported: usize,
+        bodies: &[(usize, usize)],
+    ) -> Result<(), LimitExceeded> {
+        let mut total: usize = 0;
+
+        for (i, &(instructions, depth)) in bodies.iter().enumerate() {
+            let index = Some((imported + i) as u32);
+            exceeds(Limit::BodySize, self.max_body_size, instructions, index)?;
+            exceeds(Limit::NestingDepth, self.max_nesting_depth, depth, index)?;
+            total += instructions;
+            exceeds(Limit::Instructions, self.max_instructions, total, None)?;
+        }
+        Ok(())
+    }
+}
+
+/// Walks the contents of a section, reporting it as malformed at the offset where the walk
+/// failed.
+fn walk<'a, T, F>(code: &'a [u8], header: &SectionHeader, read: F) -> Result<T, ModuleError>
+where
+    F: FnOnce(&mut Reader<'a>) -> Option<T>,
+{
+    let mut reader = Reader::new(&code[header.payload_offset..header.payload_offset + header.size]);
+    read(&mut reader).ok_or_else(|| {
+        ModuleError::Structure(StructureError {
+            kind: StructureErrorKind::MalformedSectionContents(header.id),
+            offset: header.payload_offset + reader.pos,
+        })
+    })
+}
+
+/// Returns an error if a measured quantity is above its limit.
+fn exceeds(
+    limit: Limit,
+    max: Option<usize>,
+    actual: usize,
+    function: Option<u32>,
+) -> Result<(), LimitExceeded> {
+    match max {
+        Some(max) if actual > max => Err(LimitExceeded {
+            limit,
+            max,
+            actual,
+            function,
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// Reads just enough of a section's raw contents to measure it against the limits. Every method
+/// returns None on malformed or truncated input.
+struct Reader<'a> {
+    code: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(code: &'a [u8]) -> Self {
+        Reader { code, pos: 0 }
+    }
+
+    fn byte(&mut self) -> Option<u8> {
+        let byte = *self.code.get(self.pos)?;
+        self.pos += 1;
+        Some(byte)
+    }
+
+    fn skip(&mut self, len: usize) -> Option<()> {
+        if len > self.code.len() - self.pos {
+            return None;
+        }
+        self.pos += len;
+        Some(())
+    }
+
+    fn leb(&mut self, signed: bool, bits: u32) -> Option<i128> {
+        let (value, len) = read_leb(self.code, self.pos, signed, bits).ok()?;
+        self.pos += len;
+        Some(value)
+    }
+
+    fn varuint32(&mut self) -> Option<u32> {
+        self.leb(false, 32).map(|value| value as u32)
+    }
+
+    /// Counts the function imports of an import section.
+    fn function_imports(&mut self) -> Option<usize> {
+        let mut functions: usize = 0;
+        for _ in 0..self.varuint32()? {
+            let len = self.varuint32()? as usize;
+            self.skip(len)?;
+            let len = self.varuint32()? as usize;
+            self.skip(len)?;
+            match self.byte()? {
+                0 => {
+                    self.varuint32()?;
+                    functions += 1;
+                }
+                1 => {
+                    self.byte()?;
+                    self.limits()?;
+                }
+                2 => self.limits()?,
+                3 => self.skip(2)?,
+                _ => return None,
+            }
+        }
+        Some(functions)
+    }
+
+    fn limits(&mut self) -> Option<()> {
+        let flags = self.byte()?;
+        self.varuint32()?;
+        if flags & 0x01 != 0 {
+            self.varuint32()?;
+        }
+        Some(())
+    }
+
+    /// Measures a function body, returning its number of instructions and maximum nesting depth.
+    fn body(&mut self) -> Option<(usize, usize)> {
+        let size = self.varuint32()? as usize;
+        let end = self
+            .pos
+            .checked_add(size)
+            .filter(|&end| end <= self.code.len())?;
+        for _ in 0..self.varuint32()? {
+            self.varuint32()?;
+            self.byte()?;
+        }
+
+        let mut instructions: usize = 0;
+        let mut depth: usize = 0;
+        let mut max_depth: usize = 0;
+        while self.pos < end {
+            let opcode = self.byte()?;
+            if opens_block(opcode) {
+                depth += 1;
+                max_depth = max_depth.max(depth);
+            } else if opcode == END {
+                depth = depth.saturating_sub(1);
+            }
+            for immediate in immediates(opcode)? {
+                match *immediate {
+                    Immediate::Byte => {
+                        self.byte()?;
+                    }
+                    Immediate::VarUint32(_) => {
+                        self.varuint32()?;
+                    }
+                    Immediate::VarInt32 => {
+                        self.leb(true, 32)?;
+                    }
+                    Immediate::VarInt64 => {
+                        self.leb(true, 64)?;
+                    }
+                    Immediate::Bytes(len) => self.skip(len)?,
+                    Immediate::BranchTargets => {
+                        for _ in 0..self.varuint32()? {
+                            self.varuint32()?;
+                        }
+                    }
+                }
+            }
+            instructions += 1;
+        }
+        if self.pos != end {
+            return None;
+        }
+        Some((instructions, max_depth))
+    }
+}
+
+/// Computes the maximum nesting depth of structured control instructions in a function body.
+pub fn nesting_depth(instructions: &[Instruction]) -> usize {
+    let mut depth: usize = 0;
+    let mut max: usize = 0;
+
+    for instruction in instructions {
+        match *instruction {
+            Instruction::Block(_) | Instruction::Loop(_) | Instruction::If(_) => {
+                depth += 1;
+                max = max.max(depth);
+            }
+            Instruction::End => depth = depth.saturating_sub(1),
+            _ => (),
+        }
+    }
+    max
+}
+
+#[cfg(test)]
+mod tests {
+    use limits::*;
+    use parity_wasm::elements::{BlockType, Instruction};
+    use structure::scan;
+
+    #[test]
+    fn unlimited_by_default() {
+        let limits = ResourceLimits::new();
+        assert!(limits.check_input(&vec![0u8; 1 << 20]).is_ok());
+    }
+
+    #[test]
+    fn input_bytes_limit() {
+        let limits = ResourceLimits {
+            max_input_bytes: Some(4),
+            ..ResourceLimits::default()
+        };
+        assert!(limits.check_input(&[0u8; 4]).is_ok());
+        let err = limits.check_input(&[0u8; 5]).unwrap_err();
+        assert_eq!(err.limit, Limit::InputBytes);
+        assert_eq!(err.actual, 5);
+        assert_eq!(err.max, 4);
+    }
+
+    #[test]
+    fn nested_blocks() {
+        let code = vec![
+            Instruction::Block(BlockType::NoResult),
+            Instruction::Loop(BlockType::NoResult),
+            Instruction::End,
+            Instruction::If(BlockType::NoResult),
+            Instruction::End,
+            Instruction::End,
+            Instruction::End,
+        ];
+        assert_eq!(nesting_depth(&code), 2);
+        assert_eq!(nesting_depth(&[Instruction::End]), 0);
+    }
+
+    #[test]
+    fn limits_before_deserialization() {
+        // Imports one function and defines one whose body nests a loop in a block:
+        // (block (loop (br 1))) (nop)
+        let code: Vec<u8> = vec![
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x04, 0x01, 0x60, 0x00, 0x00,
+            0x02, 0x13, 0x01, 0x08, 0x65, 0x74, 0x68, 0x65, 0x72, 0x65, 0x75, 0x6d, 0x06, 0x66,
+            0x69, 0x6e, 0x69, 0x73, 0x68, 0x00, 0x00, 0x03, 0x02, 0x01, 0x00, 0x0a, 0x0d, 0x01,
+            0x0b, 0x00, 0x02, 0x40, 0x03, 0x40, 0x0c, 0x01, 0x0b, 0x0b, 0x01, 0x0b,
+        ];
+        let sections = scan(&code).unwrap();
+        let exceeded = |limits: ResourceLimits| match limits.check_sections(&code, &sections) {
+            Err(ModuleError::ResourceLimitExceeded(e)) => (e.limit, e.actual, e.function),
+            _ => panic!("expected a limit to be exceeded"),
+        };
+
+        assert_eq!(
+            exceeded(ResourceLimits {
+                max_functions: Some(1),
+                ..ResourceLimits::default()
+            }),
+            (Limit::Functions, 2, None)
+        );
+        assert_eq!(
+            exceeded(ResourceLimits {
+                max_body_size: Some(6),
+                ..ResourceLimits::default()
+            }),
+            (Limit::BodySize, 7, Some(1))
+        );
+        assert_eq!(
+            exceeded(ResourceLimits {
+                max_nesting_depth: Some(1),
+                ..ResourceLimits::default()
+            }),
+            (Limit::NestingDepth, 2, Some(1))
+        );
+        let generous = ResourceLimits {
+            max_functions: Some(2),
+            max_body_size: Some(7),
+            max_nesting_depth: Some(2),
+            max_instructions: Some(7),
+            ..ResourceLimits::default()
+        };
+        assert!(generous.check_sections(&code, &sections).is_ok());
+
+        // An unknown opcode makes the code section malformed, reported where the walk stopped.
+        let mut malformed = code.clone();
+        *malformed.last_mut().unwrap() = 0xff;
+        let limits = ResourceLimits {
+            max_body_size: Some(0),
+            ..ResourceLimits::default()
+        };
+        match limits.check_sections(&malformed, &sections) {
+            Err(ModuleError::Structure(err)) => {
+                assert_eq!(err.kind, StructureErrorKind::MalformedSectionContents(10));
+                assert_eq!(err.offset, malformed.len());
+            }
+            _ => panic!("expected a malformed code section"),
+        }
+    }
+}
@@ -0,0 +1,446 @@
+/*
+ * libeci: Ethereum WebAssembly ABI compliance library
+ *
+ * Copyright (c) 2018 Jake Lang
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+use checker::prepare;
+use checklist::EciChecklist;
+use eci_std::chk_gas_metering;
+use eei::{ImportInterfaceMap, EEI_NAMESPACE};
+use parity_wasm::elements::{External, FunctionType, Instruction, Module, ValueType};
+use pwasm::{callable_func_type, instruction_name};
+use std::collections::HashMap;
+use transform::{add_function_import, finish, TransformError, Transformed};
+
+/// The name of the host function which charges gas.
+pub const USE_GAS: &str = "useGas";
+
+/// A straight-line run of instructions within a function body.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BasicBlock {
+    /// The callable index of the function containing the block.
+    pub function: u32,
+    /// The index of the first instruction of the block within the function body.
+    pub start: usize,
+    /// The index one past the last instruction of the block.
+    pub end: usize,
+    /// Whether the block is the body of a loop, and so may run many times per call.
+    pub loop_header: bool,
+    /// Whether the block calls `useGas`.
+    pub metered: bool,
+}
+
+/// Splits a function body into basic blocks, returned as (start, end, loop header) triples. A
+/// block ends after any instruction which enters, leaves or branches out of a structured block,
+/// except for `block`. Blocks consisting only of `end` and `else` instructions are omitted, as
+/// they do no work.
+pub fn basic_blocks(code: &[Instruction]) -> Vec<(usize, usize, bool)> {
+    let mut blocks: Vec<(usize, usize, bool)> = Vec::new();
+    let mut start = 0;
+    let mut loop_header = false;
+
+    for (i, instr) in code.iter().enumerate() {
+        let ends_block = matches!(
+            *instr,
+            Instruction::Loop(_)
+                | Instruction::If(_)
+                | Instruction::Else
+                | Instruction::End
+                | Instruction::Br(_)
+                | Instruction::BrIf(_)
+                | Instruction::BrTable(_, _)
+                | Instruction::Return
+                | Instruction::Unreachable
+        );
+        if !ends_block {
+            continue;
+        }
+
+        let structural = code[start..=i]
+            .iter()
+            .all(|instr| matches!(*instr, Instruction::End | Instruction::Else));
+        if !structural {
+            blocks.push((start, i + 1, loop_header));
+        }
+        start = i + 1;
+        loop_header = matches!(*instr, Instruction::Loop(_));
+    }
+    if start < code.len() {
+        blocks.push((start, code.len(), loop_header));
+    }
+    blocks
+}
+
+/// Returns the callable index of the module's `ethereum.useGas` import, if it imports it with the
+/// signature the interface expects.
+pub fn use_gas_index(module: &Module, eei: &ImportInterfaceMap) -> Option<u32> {
+    let expected = eei.lookup_func(EEI_NAMESPACE, USE_GAS)?;
+    let imports = module.import_section()?;
+
+    imports
+        .entries()
+        .iter()
+        .filter(|entry| matches!(*entry.external(), External::Function(_)))
+        .enumerate()
+        .find(|(func_index, entry)| {
+            entry.module() == EEI_NAMESPACE
+                && entry.field() == USE_GAS
+                && callable_func_type(module, *func_index as u32).as_ref() == Some(&expected)
+        })
+        .map(|(func_index, _)| func_index as u32)
+}
+
+/// The basic blocks of a module and whether each is metered.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MeteringCoverage {
+    blocks: Vec<BasicBlock>,
+}
+
+impl MeteringCoverage {
+    /// Analyses every function body of a module for calls to the interface's `useGas`.
+    pub fn analyse(module: &Module, eei: &ImportInterfaceMap) -> Self {
+        let use_gas = use_gas_index(module, eei);
+        let import_len = module.import_section().map_or(0, |i| i.functions());
+        let bodies = module.code_section().map_or(&[][..], |code| code.bodies());
+
+        let mut blocks: Vec<BasicBlock> = Vec::new();
+        for (body_index, body) in bodies.iter().enumerate() {
+            let code = body.code().elements();
+            for (start, end, loop_header) in basic_blocks(code) {
+                blocks.push(BasicBlock {
+                    function: (import_len + body_index) as u32,
+                    start,
+                    end,
+                    loop_header,
+                    metered: use_gas
+                        .is_some_and(|idx| code[start..end].contains(&Instruction::Call(idx))),
+                });
+            }
+        }
+        MeteringCoverage { blocks }
+    }
+
+    /// Returns every basic block of the module.
+    pub fn blocks(&self) -> &[BasicBlock] {
+        &self.blocks
+    }
+
+    /// Returns the blocks which do not call `useGas`.
+    pub fn unmetered(&self) -> Vec<&BasicBlock> {
+        self.blocks.iter().filter(|b| !b.metered).collect()
+    }
+
+    /// Returns the number of metered blocks and the total number of blocks.
+    pub fn block_coverage(&self) -> (usize, usize) {
+        (
+            self.blocks.iter().filter(|b| b.metered).count(),
+            self.blocks.len(),
+        )
+    }
+
+    /// Returns the number of metered loop headers and the total number of loop headers.
+    pub fn loop_coverage(&self) -> (usize, usize) {
+        let headers = self.blocks.iter().filter(|b| b.loop_header);
+        (
+            headers.clone().filter(|b| b.metered).count(),
+            headers.count(),
+        )
+    }
+
+    /// Returns the percentage of blocks which are metered. A module without code is fully
+    /// metered.
+    pub fn percentage(&self) -> f64 {
+        match self.block_coverage() {
+            (_, 0) => 100.0,
+            (metered, total) => metered as f64 * 100.0 / total as f64,
+        }
+    }
+
+    /// Determines whether every block is metered.
+    pub fn is_complete(&self) -> bool {
+        self.blocks.iter().all(|b| b.metered)
+    }
+}
+
+/// A metered block which charges less than its instructions cost.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MeteringDiscrepancy {
+    /// The callable index of the function containing the block.
+    pub function: u32,
+    /// The index of the first instruction of the block within the function body.
+    pub start: usize,
+    /// The index one past the last instruction of the block.
+    pub end: usize,
+    /// The constant passed to `useGas`, or None if the amount is not a non-negative constant.
+    pub charged: Option<u64>,
+    /// The cost of the block's instructions, excluding the charge itself.
+    pub expected: u64,
+}
+
+impl MeteringDiscrepancy {
+    /// Returns how much less the block charges than it costs, if the charge is constant.
+    pub fn shortfall(&self) -> Option<u64> {
+        self.charged.map(|charged| self.expected - charged)
+    }
+}
+
+/// Compares the constant passed to `useGas` in every metered block with the block's cost under
+/// the given table, returning the blocks which charge too little. The `i64.const` and call making
+/// the charge are not counted towards the cost. Unmetered blocks are not reported; see
+/// `MeteringCoverage` for those.
+pub fn undercharged_blocks(
+    module: &Module,
+    eei: &ImportInterfaceMap,
+    costs: &OpcodeCosts,
+) -> Vec<MeteringDiscrepancy> {
+    let use_gas = match use_gas_index(module, eei) {
+        Some(idx) => Instruction::Call(idx),
+        None => return Vec::new(),
+    };
+    let import_len = module.import_section().map_or(0, |i| i.functions());
+    let bodies = module.code_section().map_or(&[][..], |code| code.bodies());
+
+    let mut found: Vec<MeteringDiscrepancy> = Vec::new();
+    for (body_index, body) in bodies.iter().enumerate() {
+        let code = body.code().elements();
+        for (start, end, _) in basic_blocks(code) {
+            let block = &code[start..end];
+            let call = match block.iter().position(|instr| *instr == use_gas) {
+                Some(call) => call,
+                None => continue,
+            };
+            let (charged, charge_cost) = match call.checked_sub(1).map(|i| &block[i]) {
+                Some(&Instruction::I64Const(amount)) if amount >= 0 => (
+                    Some(amount as u64),
+                    costs.block_cost(&block[call - 1..=call]),
+                ),
+                _ => (None, costs.cost(&use_gas)),
+            };
+            let expected = costs.block_cost(block) - charge_cost;
+            if charged.is_none_or(|charged| charged < expected) {
+                found.push(MeteringDiscrepancy {
+                    function: (import_len + body_index) as u32,
+                    start,
+                    end,
+                    charged,
+                    expected,
+                });
+            }
+        }
+    }
+    found
+}
+
+/// The gas charged for each instruction when metering is injected, keyed by the instruction's
+/// text format name, e.g. "i32.div_u". Instructions without an entry cost the default.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OpcodeCosts {
+    default: u64,
+    costs: HashMap<String, u64>,
+}
+
+impl OpcodeCosts {
+    /// Constructs a table charging the same cost for every instruction.
+    pub fn new(default: u64) -> Self {
+        OpcodeCosts {
+            default,
+            costs: HashMap::new(),
+        }
+    }
+
+    /// Returns the table with the cost of one instruction overridden.
+    pub fn with_cost(mut self, name: &str, cost: u64) -> Self {
+        self.set_cost(name, cost);
+        self
+    }
+
+    /// Overrides the cost of one instruction.
+    pub fn set_cost(&mut self, name: &str, cost: u64) {
+        self.costs.insert(name.to_string(), cost);
+    }
+
+    /// Returns the cost of an instruction.
+    pub fn cost(&self, instr: &Instruction) -> u64 {
+        *self
+            .costs
+            .get(instruction_name(instr))
+            .unwrap_or(&self.default)
+    }
+
+    /// Returns the total cost of a run of instructions.
+    pub fn block_cost(&self, code: &[Instruction]) -> u64 {
+        code.iter()
+            .fold(0, |total, instr| total.saturating_add(self.cost(instr)))
+    }
+}
+
+impl Default for OpcodeCosts {
+    /// Charges one unit of gas per instruction.
+    fn default() -> Self {
+        OpcodeCosts::new(1)
+    }
+}
+
+/// Rewrites a module so that each basic block begins by charging its cost to the interface's
+/// `useGas`, which is imported if the module does not already import it. Blocks which already
+/// call `useGas` are charged again, so the input should not be metered already. The rewritten
+/// module is checked against the checklist with the gas metering check added.
+pub fn inject_metering(
+    code: &[u8],
+    costs: &OpcodeCosts,
+    checklist: &EciChecklist,
+) -> Result<Transformed, TransformError> {
+    let mut module = prepare(code, checklist)?;
+    let eei = checklist.interface();
+
+    let use_gas = match use_gas_index(&module, eei) {
+        Some(idx) => idx,
+        None => {
+            let imported = module.import_section().is_some_and(|imports| {
+                imports
+                    .entries()
+                    .iter()
+                    .any(|entry| entry.module() == EEI_NAMESPACE && entry.field() == USE_GAS)
+            });
+            if imported {
+                return Err(TransformError::Conflict(format!(
+                    "{}.{} is imported with the wrong signature",
+                    EEI_NAMESPACE, USE_GAS
+                )));
+            }
+            let signature = FunctionType::new(vec![ValueType::I64], None);
+            add_function_import(&mut module, EEI_NAMESPACE, USE_GAS, &signature)
+        }
+    };
+
+    if let Some(code) = module.code_section_mut() {
+        for body in code.bodies_mut() {
+            let instrs = body.code_mut().elements_mut();
+            for (start, end, _) in basic_blocks(instrs).into_iter().rev() {
+                let cost = costs.block_cost(&instrs[start..end]).min(i64::MAX as u64);
+                instrs.insert(start, Instruction::Call(use_gas));
+                instrs.insert(start, Instruction::I64Const(cost as i64));
+            }
+        }
+    }
+
+    let mut checklist = checklist.clone();
+    checklist.add_detailed_check("gas-metering", chk_gas_metering);
+    finish(module, code, &checklist)
+}
+
+#[cfg(test)]
+mod tests {
+    use metering::*;
+    use parity_wasm::elements::BlockType;
+    use parity_wasm::elements::Instruction::*;
+
+    #[test]
+    fn straight_line_code() {
+        assert_eq!(basic_blocks(&[I32Const(1), Drop, End]), vec![(0, 3, false)]);
+        assert!(basic_blocks(&[End]).is_empty());
+    }
+
+    #[test]
+    fn structured_code() {
+        let code = [
+            I64Const(5),                // 0
+            Call(0),                    // 1
+            Block(BlockType::NoResult), // 2
+            Loop(BlockType::NoResult),  // 3
+            GetLocal(0),                // 4
+            BrIf(1),                    // 5
+            Br(0),                      // 6
+            End,                        // 7
+            End,                        // 8
+            GetLocal(0),                // 9
+            If(BlockType::NoResult),    // 10
+            Nop,                        // 11
+            Else,                       // 12
+            Nop,                        // 13
+            End,                        // 14
+            End,                        // 15
+        ];
+        assert_eq!(
+            basic_blocks(&code),
+            vec![
+                (0, 4, false),
+                (4, 6, true),
+                (6, 7, false),
+                (9, 11, false),
+                (11, 13, false),
+                (13, 15, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn undercharged() {
+        // (import "ethereum" "useGas" (func (param i64)))
+        // (func (param i64)
+        //   (call 0 (i64.const 2)) (drop (i32.const 0)) (loop (call 0 (get_local 0)) (nop)))
+        let code: Vec<u8> = vec![
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x09, 0x02, 0x60, 0x01, 0x7e,
+            0x00, 0x60, 0x01, 0x7e, 0x00, 0x02, 0x13, 0x01, 0x08, 0x65, 0x74, 0x68, 0x65, 0x72,
+            0x65, 0x75, 0x6d, 0x06, 0x75, 0x73, 0x65, 0x47, 0x61, 0x73, 0x00, 0x00, 0x03, 0x02,
+            0x01, 0x01, 0x0a, 0x13, 0x01, 0x11, 0x00, 0x42, 0x02, 0x10, 0x00, 0x41, 0x00, 0x1a,
+            0x03, 0x40, 0x20, 0x00, 0x10, 0x00, 0x01, 0x0b, 0x0b,
+        ];
+        let module = ::parity_wasm::deserialize_buffer::<Module>(&code).unwrap();
+        let eei = ImportInterfaceMap::default();
+
+        let found = undercharged_blocks(&module, &eei, &OpcodeCosts::default());
+        assert_eq!(found.len(), 2);
+        assert_eq!(
+            found[0],
+            MeteringDiscrepancy {
+                function: 1,
+                start: 0,
+                end: 5,
+                charged: Some(2),
+                expected: 3,
+            }
+        );
+        assert_eq!(found[0].shortfall(), Some(1));
+        assert_eq!(found[1].charged, None);
+        assert_eq!(found[1].expected, 3);
+
+        let cheap = OpcodeCosts::new(0).with_cost("i32.const", 1);
+        let found = undercharged_blocks(&module, &eei, &cheap);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].shortfall(), None);
+    }
+
+    #[test]
+    fn opcode_costs() {
+        let costs = OpcodeCosts::new(1)
+            .with_cost("i64.div_u", 10)
+            .with_cost("nop", 0);
+        assert_eq!(costs.cost(&I64DivU), 10);
+        assert_eq!(costs.cost(&I64DivS), 1);
+        assert_eq!(
+            costs.block_cost(&[GetLocal(0), GetLocal(1), I64DivU, Nop]),
+            12
+        );
+        assert_eq!(OpcodeCosts::default().block_cost(&[]), 0);
+    }
+}
@@ -0,0 +1,154 @@
+/*
+ * libeci: Ethereum WebAssembly ABI compliance library
+ *
+ * Copyright (c) 2018 Jake Lang
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+use eei::{DEBUG_NAMESPACE, EEI_NAMESPACE};
+use metering::OpcodeCosts;
+
+/// The kind of network a profile targets, which decides how strictly debugging aids are treated.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Mode {
+    Testnet,
+    Production,
+}
+
+/// A deployment profile, describing which import namespaces a module may use and what its
+/// instructions cost.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Profile {
+    name: String,
+    mode: Mode,
+    namespaces: Vec<String>,
+    opcode_costs: OpcodeCosts,
+}
+
+impl Profile {
+    /// Constructs a production profile with the given name which permits no namespaces.
+    pub fn new(name: &str) -> Self {
+        Profile {
+            name: name.to_string(),
+            mode: Mode::Production,
+            namespaces: Vec::new(),
+            opcode_costs: OpcodeCosts::default(),
+        }
+    }
+
+    /// Constructs a testnet profile, which permits the "ethereum" and "debug" namespaces.
+    pub fn testnet() -> Self {
+        Profile::new("testnet")
+            .with_mode(Mode::Testnet)
+            .permit_namespace(EEI_NAMESPACE)
+            .permit_namespace(DEBUG_NAMESPACE)
+    }
+
+    /// Constructs a production profile, which only permits the "ethereum" namespace.
+    pub fn production() -> Self {
+        Profile::new("production").permit_namespace(EEI_NAMESPACE)
+    }
+
+    /// Sets the kind of network the profile targets.
+    pub fn with_mode(mut self, mode: Mode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Permits imports from the given namespace.
+    pub fn permit_namespace(mut self, namespace: &str) -> Self {
+        if !self.permits_namespace(namespace) {
+            self.namespaces.push(namespace.to_string());
+        }
+        self
+    }
+
+    /// Sets the instruction costs metering is expected to charge.
+    pub fn with_opcode_costs(mut self, costs: OpcodeCosts) -> Self {
+        self.opcode_costs = costs;
+        self
+    }
+
+    /// Returns the name of the profile.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the kind of network the profile targets.
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    /// Returns the namespaces imports are permitted from.
+    pub fn namespaces(&self) -> &[String] {
+        &self.namespaces
+    }
+
+    /// Determines whether imports from the given namespace are permitted.
+    pub fn permits_namespace(&self, namespace: &str) -> bool {
+        self.namespaces.iter().any(|ns| ns == namespace)
+    }
+
+    /// Returns the instruction costs metering is expected to charge.
+    pub fn opcode_costs(&self) -> &OpcodeCosts {
+        &self.opcode_costs
+    }
+}
+
+impl Default for Profile {
+    /// Constructs a production profile which only permits imports from the "ethereum" namespace.
+    fn default() -> Self {
+        Profile::new("default").permit_namespace(EEI_NAMESPACE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use profile::*;
+
+    #[test]
+    fn default_profile() {
+        let profile = Profile::default();
+        assert!(profile.permits_namespace("ethereum"));
+        assert!(!profile.permits_namespace("debug"));
+        assert_eq!(profile.mode(), Mode::Production);
+    }
+
+    #[test]
+    fn testnet_and_production() {
+        let testnet = Profile::testnet();
+        assert_eq!(testnet.mode(), Mode::Testnet);
+        assert!(testnet.permits_namespace("debug"));
+        let production = Profile::production();
+        assert_eq!(production.mode(), Mode::Production);
+        assert!(!production.permits_namespace("debug"));
+    }
+
+    #[test]
+    fn permit_namespaces() {
+        let profile = Profile::new("library")
+            .permit_namespace("ethereum")
+            .permit_namespace("keccak")
+            .permit_namespace("keccak");
+        assert_eq!(profile.name(), "library");
+        assert_eq!(profile.namespaces().len(), 2);
+        assert!(profile.permits_namespace("keccak"));
+    }
+}
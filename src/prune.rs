@@ -0,0 +1,220 @@
+/*
+ * libeci: Ethereum WebAssembly ABI compliance library
+ *
+ * Copyright (c) 2018 Jake Lang
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+use callgraph::CallGraph;
+use checker::prepare;
+use checklist::EciChecklist;
+use parity_wasm::elements::Module;
+use std::collections::BTreeSet;
+use transform::{finish, remove_functions, remove_unused_types, TransformError, Transformed};
+
+/// Returns the callable indices of defined functions which cannot run: those not reachable from
+/// an exported function, the start function or a table element.
+pub fn unreachable_functions(module: &Module) -> Vec<u32> {
+    unreachable_in(&CallGraph::build(module))
+}
+
+/// Returns the defined functions a call graph cannot reach from its roots.
+pub(crate) fn unreachable_in(graph: &CallGraph) -> Vec<u32> {
+    let live = graph.live_functions();
+    (0..graph.len() as u32)
+        .filter(|&f| !graph.is_import(f) && !live.contains(&f))
+        .collect()
+}
+
+/// Rejects modules calling functions which do not exist, as renumbering would silently retarget
+/// those calls.
+fn reject_dangling_calls(graph: &CallGraph) -> Result<(), TransformError> {
+    match graph.dangling_calls().first() {
+        Some(&(caller, callee)) => Err(TransformError::Invalid(format!(
+            "function {} calls function {}, which does not exist",
+            caller, callee
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Returns the callable indices of imported functions which nothing calls, exports or places in
+/// the table.
+pub fn unused_imports(module: &Module) -> Vec<u32> {
+    unused_in(&CallGraph::build(module))
+}
+
+/// Returns the imported functions of a call graph which nothing calls or refers to.
+pub(crate) fn unused_in(graph: &CallGraph) -> Vec<u32> {
+    (0..graph.len() as u32)
+        .filter(|&f| graph.is_import(f))
+        .filter(|f| graph.callers(*f).is_empty() && !graph.roots().contains(f))
+        .collect()
+}
+
+/// Rewrites a module without its unused imports, renumbering every function reference. The
+/// rewritten module is checked against the checklist.
+pub fn strip_unused_imports(
+    code: &[u8],
+    checklist: &EciChecklist,
+) -> Result<Transformed, TransformError> {
+    let mut module = prepare(code, checklist)?;
+    let graph = CallGraph::build(&module);
+    reject_dangling_calls(&graph)?;
+    let unused: BTreeSet<u32> = unused_in(&graph).into_iter().collect();
+    remove_functions(&mut module, &unused);
+    finish(module, code, checklist)
+}
+
+/// Rewrites a module without its unreachable functions, the imports only they call and the
+/// function types nothing refers to any more. The rewritten module is checked against the
+/// checklist.
+pub fn eliminate_dead_code(
+    code: &[u8],
+    checklist: &EciChecklist,
+) -> Result<Transformed, TransformError> {
+    let mut module = prepare(code, checklist)?;
+    let graph = CallGraph::build(&module);
+    reject_dangling_calls(&graph)?;
+    let live = graph.live_functions();
+    let dead: BTreeSet<u32> = (0..graph.len() as u32)
+        .filter(|f| !live.contains(f))
+        .collect();
+
+    remove_functions(&mut module, &dead);
+    remove_unused_types(&mut module);
+    finish(module, code, checklist)
+}
+
+#[cfg(test)]
+mod tests {
+    use checker::check;
+    use checklist::{CheckStatus, Severity};
+    use parity_wasm::elements::{deserialize_buffer, FunctionType, Instruction, ValueType};
+    use parity_wasm::serialize;
+    use prune::*;
+    use transform::add_function_import;
+
+    // Imports useGas (0) and finish (1); main (2) calls c (4), which calls finish; b (3) is dead
+    // and is the only user of useGas and of its own type.
+    const CODE: &[u8] = &[
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x11, 0x04, 0x60, 0x01, 0x7e, 0x00,
+        0x60, 0x02, 0x7f, 0x7f, 0x00, 0x60, 0x00, 0x00, 0x60, 0x01, 0x7d, 0x00, 0x02, 0x25, 0x02,
+        0x08, 0x65, 0x74, 0x68, 0x65, 0x72, 0x65, 0x75, 0x6d, 0x06, 0x75, 0x73, 0x65, 0x47, 0x61,
+        0x73, 0x00, 0x00, 0x08, 0x65, 0x74, 0x68, 0x65, 0x72, 0x65, 0x75, 0x6d, 0x06, 0x66, 0x69,
+        0x6e, 0x69, 0x73, 0x68, 0x00, 0x01, 0x03, 0x04, 0x03, 0x02, 0x03, 0x02, 0x05, 0x03, 0x01,
+        0x00, 0x01, 0x07, 0x11, 0x02, 0x04, 0x6d, 0x61, 0x69, 0x6e, 0x00, 0x02, 0x06, 0x6d, 0x65,
+        0x6d, 0x6f, 0x72, 0x79, 0x02, 0x00, 0x0a, 0x16, 0x03, 0x04, 0x00, 0x10, 0x04, 0x0b, 0x06,
+        0x00, 0x42, 0x01, 0x10, 0x00, 0x0b, 0x08, 0x00, 0x41, 0x00, 0x41, 0x00, 0x10, 0x01, 0x0b,
+    ];
+
+    #[test]
+    fn unreachable() {
+        let module = deserialize_buffer::<Module>(CODE).unwrap();
+        assert_eq!(unreachable_functions(&module), vec![3]);
+
+        let report = check(CODE, &EciChecklist::default());
+        assert_eq!(
+            report.status("unreachable-functions"),
+            Some(CheckStatus::Good)
+        );
+        let findings = report.findings("unreachable-functions").unwrap();
+        assert_eq!(
+            findings[0].message(),
+            "function 3 is never reachable from an export, the start function or the table"
+        );
+        assert_eq!(findings[0].severity(), Severity::Warning);
+    }
+
+    #[test]
+    fn dead_code_is_removed() {
+        let pruned = eliminate_dead_code(CODE, &EciChecklist::default()).unwrap();
+        assert!(pruned.report().is_good());
+        assert!(pruned.bytes_saved() > 0);
+
+        let module = deserialize_buffer::<Module>(pruned.code()).unwrap();
+        assert!(unreachable_functions(&module).is_empty());
+        let imports = module.import_section().unwrap().entries();
+        assert_eq!(imports.len(), 1);
+        assert_eq!(imports[0].field(), "finish");
+        assert_eq!(module.type_section().unwrap().types().len(), 2);
+        let bodies = module.code_section().unwrap().bodies();
+        assert_eq!(bodies.len(), 2);
+        assert_eq!(bodies[0].code().elements()[0], Instruction::Call(2));
+        assert_eq!(bodies[1].code().elements()[2], Instruction::Call(0));
+    }
+
+    #[test]
+    fn unused_imports_are_stripped() {
+        let module = deserialize_buffer::<Module>(CODE).unwrap();
+        assert!(unused_imports(&module).is_empty());
+
+        // Once dead code is gone, nothing calls useGas any more.
+        let pruned = eliminate_dead_code(CODE, &EciChecklist::new()).unwrap();
+        let mut module = deserialize_buffer::<Module>(pruned.code()).unwrap();
+        let signature = FunctionType::new(vec![ValueType::I64], None);
+        add_function_import(&mut module, "ethereum", "useGas", &signature);
+        let code = serialize(module).unwrap();
+        let module = deserialize_buffer::<Module>(&code).unwrap();
+        assert_eq!(unused_imports(&module), vec![1]);
+
+        let report = check(&code, &EciChecklist::default());
+        assert_eq!(report.status("unused-imports"), Some(CheckStatus::Good));
+        assert_eq!(
+            report.findings("unused-imports").unwrap()[0].message(),
+            "ethereum.useGas is imported but never called"
+        );
+
+        let stripped = strip_unused_imports(&code, &EciChecklist::default()).unwrap();
+        assert!(stripped
+            .report()
+            .findings("unused-imports")
+            .unwrap()
+            .is_empty());
+        assert!(stripped.bytes_saved() > 0);
+        let module = deserialize_buffer::<Module>(stripped.code()).unwrap();
+        assert_eq!(module.import_section().unwrap().functions(), 1);
+        let bodies = module.code_section().unwrap().bodies();
+        assert_eq!(bodies[0].code().elements()[0], Instruction::Call(2));
+        assert_eq!(bodies[1].code().elements()[2], Instruction::Call(0));
+    }
+
+    #[test]
+    fn dangling_calls_are_rejected() {
+        // (func (call 5)) exported as main, where function 5 does not exist.
+        let code: Vec<u8> = vec![
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x04, 0x01, 0x60, 0x00, 0x00,
+            0x03, 0x02, 0x01, 0x00, 0x07, 0x08, 0x01, 0x04, 0x6d, 0x61, 0x69, 0x6e, 0x00, 0x00,
+            0x0a, 0x06, 0x01, 0x04, 0x00, 0x10, 0x05, 0x0b,
+        ];
+        assert_eq!(
+            strip_unused_imports(&code, &EciChecklist::new()).unwrap_err(),
+            TransformError::Invalid(
+                "function 0 calls function 5, which does not exist".to_string()
+            )
+        );
+        assert_eq!(
+            eliminate_dead_code(&code, &EciChecklist::new()).unwrap_err(),
+            TransformError::Invalid(
+                "function 0 calls function 5, which does not exist".to_string()
+            )
+        );
+    }
+}
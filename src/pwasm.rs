@@ -22,7 +22,9 @@
  * SOFTWARE.
  */
 
-use parity_wasm::elements::{External, FunctionType, Internal, Module, Type};
+use parity_wasm::elements::{
+    External, FunctionType, Instruction, Internal, Module, Type, ValueType,
+};
 
 /// Resolves a function's signature from its callable index. Borrowed from parity-wasm examples.
 pub fn func_type_by_index(module: &Module, index: usize) -> FunctionType {
@@ -32,14 +34,7 @@ pub fn func_type_by_index(module: &Module, index: usize) -> FunctionType {
     let type_section = module.type_section().expect("No type section found");
 
     let import_section_len: usize = match module.import_section() {
-        Some(import) => import
-            .entries()
-            .iter()
-            .filter(|entry| match entry.external() {
-                &External::Function(_) => true,
-                _ => false,
-            })
-            .count(),
+        Some(import) => import.functions(),
         None => 0,
     };
 
@@ -60,7 +55,7 @@ pub fn imported_func_type_by_index(module: &Module, index: usize) -> FunctionTyp
 
     let func_type_ref: usize = match import_section.entries()[index].external() {
         &External::Function(idx) => idx as usize,
-        _ => usize::max_value(),
+        _ => usize::MAX,
     };
 
     match type_section.types()[func_type_ref] {
@@ -68,6 +63,32 @@ pub fn imported_func_type_by_index(module: &Module, index: usize) -> FunctionTyp
     }
 }
 
+/// Resolves the signature of any function, imported or defined, from its callable index.
+pub fn callable_func_type(module: &Module, index: u32) -> Option<FunctionType> {
+    let types = module.type_section()?.types();
+    let imports = module.import_section().map_or(&[][..], |i| i.entries());
+    let import_len = module.import_section().map_or(0, |i| i.functions());
+
+    let type_ref = if (index as usize) < import_len {
+        imports
+            .iter()
+            .filter_map(|entry| match *entry.external() {
+                External::Function(type_ref) => Some(type_ref),
+                _ => None,
+            })
+            .nth(index as usize)?
+    } else {
+        module
+            .function_section()?
+            .entries()
+            .get(index as usize - import_len)?
+            .type_ref()
+    };
+    match *types.get(type_ref as usize)? {
+        Type::Function(ref func_type) => Some(func_type.clone()),
+    }
+}
+
 /// Resolves an export name to a tuple containing its callable index and internal reference.
 pub fn resolve_export_by_name(module: &Module, name: &str) -> Option<(u32, Internal)> {
     if !has_export_section(module) {
@@ -78,7 +99,7 @@ pub fn resolve_export_by_name(module: &Module, name: &str) -> Option<(u32, Inter
             .unwrap()
             .entries()
             .iter()
-            .find(|export| if export.field() == name { true } else { false })
+            .find(|export| export.field() == name)
         {
             Some(export) => match *export.internal() {
                 //halp
@@ -96,7 +117,7 @@ pub fn resolve_export_by_name(module: &Module, name: &str) -> Option<(u32, Inter
 /// Parses a module's import section into a list of tuples containing the import fields.
 pub fn get_imports(module: &Module) -> Option<Vec<(&str, &str)>> {
     if !has_import_section(module) {
-        return None;
+        None
     } else {
         let imports_list: Option<Vec<(&str, &str)>> = Some(
             module
@@ -111,18 +132,234 @@ pub fn get_imports(module: &Module) -> Option<Vec<(&str, &str)>> {
     }
 }
 
+/// Finds every direct call to a function, returning the callable index of each calling function
+/// along with the index of the call instruction within its body.
+pub fn find_call_sites(module: &Module, callee: u32) -> Vec<(u32, usize)> {
+    let import_len: u32 = match module.import_section() {
+        Some(import) => import.functions() as u32,
+        None => 0,
+    };
+    let bodies = match module.code_section() {
+        Some(code) => code.bodies(),
+        None => return Vec::new(),
+    };
+
+    let mut sites: Vec<(u32, usize)> = Vec::new();
+    for (body_index, body) in bodies.iter().enumerate() {
+        for (instr_index, instr) in body.code().elements().iter().enumerate() {
+            if *instr == Instruction::Call(callee) {
+                sites.push((import_len + body_index as u32, instr_index));
+            }
+        }
+    }
+    sites
+}
+
+/// Returns the text format name of an instruction, e.g. "i32.load8_u".
+pub fn instruction_name(instr: &Instruction) -> &'static str {
+    match *instr {
+        Instruction::Unreachable => "unreachable",
+        Instruction::Nop => "nop",
+        Instruction::Block(..) => "block",
+        Instruction::Loop(..) => "loop",
+        Instruction::If(..) => "if",
+        Instruction::Else => "else",
+        Instruction::End => "end",
+        Instruction::Br(..) => "br",
+        Instruction::BrIf(..) => "br_if",
+        Instruction::BrTable(..) => "br_table",
+        Instruction::Return => "return",
+        Instruction::Call(..) => "call",
+        Instruction::CallIndirect(..) => "call_indirect",
+        Instruction::Drop => "drop",
+        Instruction::Select => "select",
+        Instruction::GetLocal(..) => "get_local",
+        Instruction::SetLocal(..) => "set_local",
+        Instruction::TeeLocal(..) => "tee_local",
+        Instruction::GetGlobal(..) => "get_global",
+        Instruction::SetGlobal(..) => "set_global",
+        Instruction::I32Load(..) => "i32.load",
+        Instruction::I64Load(..) => "i64.load",
+        Instruction::F32Load(..) => "f32.load",
+        Instruction::F64Load(..) => "f64.load",
+        Instruction::I32Load8S(..) => "i32.load8_s",
+        Instruction::I32Load8U(..) => "i32.load8_u",
+        Instruction::I32Load16S(..) => "i32.load16_s",
+        Instruction::I32Load16U(..) => "i32.load16_u",
+        Instruction::I64Load8S(..) => "i64.load8_s",
+        Instruction::I64Load8U(..) => "i64.load8_u",
+        Instruction::I64Load16S(..) => "i64.load16_s",
+        Instruction::I64Load16U(..) => "i64.load16_u",
+        Instruction::I64Load32S(..) => "i64.load32_s",
+        Instruction::I64Load32U(..) => "i64.load32_u",
+        Instruction::I32Store(..) => "i32.store",
+        Instruction::I64Store(..) => "i64.store",
+        Instruction::F32Store(..) => "f32.store",
+        Instruction::F64Store(..) => "f64.store",
+        Instruction::I32Store8(..) => "i32.store8",
+        Instruction::I32Store16(..) => "i32.store16",
+        Instruction::I64Store8(..) => "i64.store8",
+        Instruction::I64Store16(..) => "i64.store16",
+        Instruction::I64Store32(..) => "i64.store32",
+        Instruction::CurrentMemory(..) => "current_memory",
+        Instruction::GrowMemory(..) => "grow_memory",
+        Instruction::I32Const(..) => "i32.const",
+        Instruction::I64Const(..) => "i64.const",
+        Instruction::F32Const(..) => "f32.const",
+        Instruction::F64Const(..) => "f64.const",
+        Instruction::I32Eqz => "i32.eqz",
+        Instruction::I32Eq => "i32.eq",
+        Instruction::I32Ne => "i32.ne",
+        Instruction::I32LtS => "i32.lt_s",
+        Instruction::I32LtU => "i32.lt_u",
+        Instruction::I32GtS => "i32.gt_s",
+        Instruction::I32GtU => "i32.gt_u",
+        Instruction::I32LeS => "i32.le_s",
+        Instruction::I32LeU => "i32.le_u",
+        Instruction::I32GeS => "i32.ge_s",
+        Instruction::I32GeU => "i32.ge_u",
+        Instruction::I64Eqz => "i64.eqz",
+        Instruction::I64Eq => "i64.eq",
+        Instruction::I64Ne => "i64.ne",
+        Instruction::I64LtS => "i64.lt_s",
+        Instruction::I64LtU => "i64.lt_u",
+        Instruction::I64GtS => "i64.gt_s",
+        Instruction::I64GtU => "i64.gt_u",
+        Instruction::I64LeS => "i64.le_s",
+        Instruction::I64LeU => "i64.le_u",
+        Instruction::I64GeS => "i64.ge_s",
+        Instruction::I64GeU => "i64.ge_u",
+        Instruction::F32Eq => "f32.eq",
+        Instruction::F32Ne => "f32.ne",
+        Instruction::F32Lt => "f32.lt",
+        Instruction::F32Gt => "f32.gt",
+        Instruction::F32Le => "f32.le",
+        Instruction::F32Ge => "f32.ge",
+        Instruction::F64Eq => "f64.eq",
+        Instruction::F64Ne => "f64.ne",
+        Instruction::F64Lt => "f64.lt",
+        Instruction::F64Gt => "f64.gt",
+        Instruction::F64Le => "f64.le",
+        Instruction::F64Ge => "f64.ge",
+        Instruction::I32Clz => "i32.clz",
+        Instruction::I32Ctz => "i32.ctz",
+        Instruction::I32Popcnt => "i32.popcnt",
+        Instruction::I32Add => "i32.add",
+        Instruction::I32Sub => "i32.sub",
+        Instruction::I32Mul => "i32.mul",
+        Instruction::I32DivS => "i32.div_s",
+        Instruction::I32DivU => "i32.div_u",
+        Instruction::I32RemS => "i32.rem_s",
+        Instruction::I32RemU => "i32.rem_u",
+        Instruction::I32And => "i32.and",
+        Instruction::I32Or => "i32.or",
+        Instruction::I32Xor => "i32.xor",
+        Instruction::I32Shl => "i32.shl",
+        Instruction::I32ShrS => "i32.shr_s",
+        Instruction::I32ShrU => "i32.shr_u",
+        Instruction::I32Rotl => "i32.rotl",
+        Instruction::I32Rotr => "i32.rotr",
+        Instruction::I64Clz => "i64.clz",
+        Instruction::I64Ctz => "i64.ctz",
+        Instruction::I64Popcnt => "i64.popcnt",
+        Instruction::I64Add => "i64.add",
+        Instruction::I64Sub => "i64.sub",
+        Instruction::I64Mul => "i64.mul",
+        Instruction::I64DivS => "i64.div_s",
+        Instruction::I64DivU => "i64.div_u",
+        Instruction::I64RemS => "i64.rem_s",
+        Instruction::I64RemU => "i64.rem_u",
+        Instruction::I64And => "i64.and",
+        Instruction::I64Or => "i64.or",
+        Instruction::I64Xor => "i64.xor",
+        Instruction::I64Shl => "i64.shl",
+        Instruction::I64ShrS => "i64.shr_s",
+        Instruction::I64ShrU => "i64.shr_u",
+        Instruction::I64Rotl => "i64.rotl",
+        Instruction::I64Rotr => "i64.rotr",
+        Instruction::F32Abs => "f32.abs",
+        Instruction::F32Neg => "f32.neg",
+        Instruction::F32Ceil => "f32.ceil",
+        Instruction::F32Floor => "f32.floor",
+        Instruction::F32Trunc => "f32.trunc",
+        Instruction::F32Nearest => "f32.nearest",
+        Instruction::F32Sqrt => "f32.sqrt",
+        Instruction::F32Add => "f32.add",
+        Instruction::F32Sub => "f32.sub",
+        Instruction::F32Mul => "f32.mul",
+        Instruction::F32Div => "f32.div",
+        Instruction::F32Min => "f32.min",
+        Instruction::F32Max => "f32.max",
+        Instruction::F32Copysign => "f32.copysign",
+        Instruction::F64Abs => "f64.abs",
+        Instruction::F64Neg => "f64.neg",
+        Instruction::F64Ceil => "f64.ceil",
+        Instruction::F64Floor => "f64.floor",
+        Instruction::F64Trunc => "f64.trunc",
+        Instruction::F64Nearest => "f64.nearest",
+        Instruction::F64Sqrt => "f64.sqrt",
+        Instruction::F64Add => "f64.add",
+        Instruction::F64Sub => "f64.sub",
+        Instruction::F64Mul => "f64.mul",
+        Instruction::F64Div => "f64.div",
+        Instruction::F64Min => "f64.min",
+        Instruction::F64Max => "f64.max",
+        Instruction::F64Copysign => "f64.copysign",
+        Instruction::I32WrapI64 => "i32.wrap/i64",
+        Instruction::I32TruncSF32 => "i32.trunc_s/f32",
+        Instruction::I32TruncUF32 => "i32.trunc_u/f32",
+        Instruction::I32TruncSF64 => "i32.trunc_s/f64",
+        Instruction::I32TruncUF64 => "i32.trunc_u/f64",
+        Instruction::I64ExtendSI32 => "i64.extend_s/i32",
+        Instruction::I64ExtendUI32 => "i64.extend_u/i32",
+        Instruction::I64TruncSF32 => "i64.trunc_s/f32",
+        Instruction::I64TruncUF32 => "i64.trunc_u/f32",
+        Instruction::I64TruncSF64 => "i64.trunc_s/f64",
+        Instruction::I64TruncUF64 => "i64.trunc_u/f64",
+        Instruction::F32ConvertSI32 => "f32.convert_s/i32",
+        Instruction::F32ConvertUI32 => "f32.convert_u/i32",
+        Instruction::F32ConvertSI64 => "f32.convert_s/i64",
+        Instruction::F32ConvertUI64 => "f32.convert_u/i64",
+        Instruction::F32DemoteF64 => "f32.demote/f64",
+        Instruction::F64ConvertSI32 => "f64.convert_s/i32",
+        Instruction::F64ConvertUI32 => "f64.convert_u/i32",
+        Instruction::F64ConvertSI64 => "f64.convert_s/i64",
+        Instruction::F64ConvertUI64 => "f64.convert_u/i64",
+        Instruction::F64PromoteF32 => "f64.promote/f32",
+        Instruction::I32ReinterpretF32 => "i32.reinterpret/f32",
+        Instruction::I64ReinterpretF64 => "i64.reinterpret/f64",
+        Instruction::F32ReinterpretI32 => "f32.reinterpret/i32",
+        Instruction::F64ReinterpretI64 => "f64.reinterpret/i64",
+    }
+}
+
 /// Determines whether a module has an export section.
 pub fn has_export_section(module: &Module) -> bool {
-    match module.export_section() {
-        Some(_thing) => true,
-        None => false,
-    }
+    module.export_section().is_some()
 }
 
 /// Determines whether a module has an import section.
 pub fn has_import_section(module: &Module) -> bool {
-    match module.import_section() {
-        Some(_thing) => true,
-        None => false,
+    module.import_section().is_some()
+}
+
+/// Returns the text format name of a value type.
+pub fn value_type_name(value_type: ValueType) -> &'static str {
+    match value_type {
+        ValueType::I32 => "i32",
+        ValueType::I64 => "i64",
+        ValueType::F32 => "f32",
+        ValueType::F64 => "f64",
+    }
+}
+
+/// Formats a function signature as a parameter list and optional return type, e.g.
+/// "(i64, i32) -> i32".
+pub fn func_type_to_string(sig: &FunctionType) -> String {
+    let params: Vec<&str> = sig.params().iter().map(|p| value_type_name(*p)).collect();
+
+    match sig.return_type() {
+        Some(ret) => format!("({}) -> {}", params.join(", "), value_type_name(ret)),
+        None => format!("({})", params.join(", ")),
     }
 }
@@ -0,0 +1,245 @@
+/*
+ * libeci: Ethereum WebAssembly ABI compliance library
+ *
+ * Copyright (c) 2018 Jake Lang
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+use checklist::Finding;
+use eci_std::import_mismatch;
+use parity_wasm::elements::{External, Module};
+use pwasm::callable_func_type;
+use spec::InterfaceSpec;
+use std::fmt;
+
+/// The EEI revisions a module's imports are compatible with, and the imports ruling out the rest.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RevisionReport {
+    compatible: Vec<u32>,
+    conflicts: Vec<(u32, Vec<Finding>)>,
+}
+
+impl RevisionReport {
+    /// Matches a module's imports against every revision of the built-in EEI spec.
+    pub fn detect(module: &Module) -> Self {
+        RevisionReport::detect_with(module, InterfaceSpec::builtin())
+    }
+
+    /// Matches a module's imports against every revision of the given interface spec.
+    pub fn detect_with(module: &Module, spec: &InterfaceSpec) -> Self {
+        let mut report = RevisionReport {
+            compatible: Vec::new(),
+            conflicts: Vec::new(),
+        };
+
+        for rev in spec.revisions() {
+            let eei = spec.build(rev);
+            let mut findings: Vec<Finding> = Vec::new();
+
+            if let Some(imports) = module.import_section() {
+                let func_imports = imports
+                    .entries()
+                    .iter()
+                    .filter(|entry| matches!(*entry.external(), External::Function(_)));
+
+                for (func_index, entry) in func_imports.enumerate() {
+                    // Namespaces outside the spec do not tell revisions apart.
+                    if !eei.has_namespace(entry.module()) {
+                        continue;
+                    }
+                    let mismatch = match callable_func_type(module, func_index as u32) {
+                        Some(funcsig) => {
+                            import_mismatch(&eei, entry.module(), entry.field(), &funcsig)
+                        }
+                        None => Some(format!(
+                            "{}.{} refers to a type which does not exist",
+                            entry.module(),
+                            entry.field()
+                        )),
+                    };
+                    if let Some(mismatch) = mismatch {
+                        findings.push(Finding::new(&mismatch).in_function(func_index as u32));
+                    }
+                }
+            }
+
+            if findings.is_empty() {
+                report.compatible.push(rev);
+            } else {
+                report.conflicts.push((rev, findings));
+            }
+        }
+
+        report
+    }
+
+    /// Returns the revisions the module is compatible with, in ascending order.
+    pub fn compatible(&self) -> &[u32] {
+        &self.compatible
+    }
+
+    /// Determines whether the module is compatible with the given revision.
+    pub fn is_compatible(&self, rev: u32) -> bool {
+        self.compatible.contains(&rev)
+    }
+
+    /// Returns the latest compatible revision. If there is none, returns the revision with the
+    /// fewest conflicting imports, preferring later revisions.
+    pub fn best_match(&self) -> Option<u32> {
+        match self.compatible.last() {
+            Some(rev) => Some(*rev),
+            None => self
+                .conflicts
+                .iter()
+                .rev()
+                .min_by_key(|(_, findings)| findings.len())
+                .map(|(rev, _)| *rev),
+        }
+    }
+
+    /// Returns the imports which rule out the given revision. Empty if the module is compatible
+    /// with it.
+    pub fn conflicts(&self, rev: u32) -> &[Finding] {
+        self.conflicts
+            .iter()
+            .find(|(r, _)| *r == rev)
+            .map(|(_, findings)| findings.as_slice())
+            .unwrap_or(&[])
+    }
+}
+
+impl fmt::Display for RevisionReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.best_match() {
+            Some(rev) if self.is_compatible(rev) => {
+                writeln!(f, "best match: EEI revision {}", rev)?
+            }
+            Some(rev) => writeln!(f, "best match: EEI revision {} (incompatible)", rev)?,
+            None => writeln!(f, "best match: none")?,
+        }
+        for (rev, findings) in self.conflicts.iter() {
+            writeln!(f, "EEI revision {} is ruled out by:", rev)?;
+            for finding in findings.iter() {
+                writeln!(f, "    {}", finding.message())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use parity_wasm::deserialize_buffer;
+    use parity_wasm::elements::Module;
+    use revision::*;
+
+    // An interface which adds ethereum.finish in revision 2 and removes ethereum.getBalance in
+    // revision 3.
+    const HISTORY: &str = "revision 1\n\
+                           namespace ethereum\n\
+                           getBalance(i32, i32)\n\
+                           revision 2\n\
+                           namespace ethereum\n\
+                           finish(i32, i32)\n\
+                           revision 3\n\
+                           namespace ethereum\n\
+                           remove getBalance\n\
+                           getExternalBalance(i32, i32)";
+
+    // Imports ethereum.getBalance, which was removed in revision 3.
+    const GET_BALANCE: &[u8] = &[
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x09, 0x02, 0x60, 0x02, 0x7f, 0x7f,
+        0x00, 0x60, 0x00, 0x00, 0x02, 0x17, 0x01, 0x08, 0x65, 0x74, 0x68, 0x65, 0x72, 0x65, 0x75,
+        0x6d, 0x0a, 0x67, 0x65, 0x74, 0x42, 0x61, 0x6c, 0x61, 0x6e, 0x63, 0x65, 0x00, 0x00, 0x03,
+        0x02, 0x01, 0x01, 0x07, 0x08, 0x01, 0x04, 0x6d, 0x61, 0x69, 0x6e, 0x00, 0x01, 0x0a, 0x04,
+        0x01, 0x02, 0x00, 0x0b,
+    ];
+
+    // Imports ethereum.getBalance and ethereum.finish, which was added in revision 2.
+    const GET_BALANCE_AND_FINISH: &[u8] = &[
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x09, 0x02, 0x60, 0x02, 0x7f, 0x7f,
+        0x00, 0x60, 0x00, 0x00, 0x02, 0x29, 0x02, 0x08, 0x65, 0x74, 0x68, 0x65, 0x72, 0x65, 0x75,
+        0x6d, 0x0a, 0x67, 0x65, 0x74, 0x42, 0x61, 0x6c, 0x61, 0x6e, 0x63, 0x65, 0x00, 0x00, 0x08,
+        0x65, 0x74, 0x68, 0x65, 0x72, 0x65, 0x75, 0x6d, 0x06, 0x66, 0x69, 0x6e, 0x69, 0x73, 0x68,
+        0x00, 0x00, 0x03, 0x02, 0x01, 0x01, 0x07, 0x08, 0x01, 0x04, 0x6d, 0x61, 0x69, 0x6e, 0x00,
+        0x02, 0x0a, 0x04, 0x01, 0x02, 0x00, 0x0b,
+    ];
+
+    fn module(code: &[u8]) -> Module {
+        deserialize_buffer::<Module>(code).unwrap()
+    }
+
+    fn detect(code: &[u8]) -> RevisionReport {
+        RevisionReport::detect_with(&module(code), &InterfaceSpec::parse(HISTORY).unwrap())
+    }
+
+    #[test]
+    fn removed_function_rules_out_later_revisions() {
+        let report = detect(GET_BALANCE);
+        assert_eq!(report.compatible(), &[1, 2]);
+        assert_eq!(report.best_match(), Some(2));
+        assert!(report.conflicts(2).is_empty());
+        let conflicts = report.conflicts(3);
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(
+            conflicts[0].message(),
+            "ethereum.getBalance was removed in EEI revision 3"
+        );
+        assert_eq!(conflicts[0].function(), Some(0));
+    }
+
+    #[test]
+    fn added_function_rules_out_earlier_revisions() {
+        let report = detect(GET_BALANCE_AND_FINISH);
+        assert_eq!(report.compatible(), &[2]);
+        assert_eq!(report.best_match(), Some(2));
+        assert_eq!(
+            report.conflicts(1)[0].message(),
+            "ethereum.finish is not a function of EEI revision 1"
+        );
+        assert_eq!(report.conflicts(1)[0].function(), Some(1));
+        assert_eq!(report.conflicts(3).len(), 1);
+    }
+
+    #[test]
+    fn builtin_revision() {
+        let report = RevisionReport::detect(&module(GET_BALANCE));
+        assert!(report.compatible().is_empty());
+        assert_eq!(report.best_match(), Some(3));
+        assert_eq!(
+            report.conflicts(3)[0].message(),
+            "ethereum.getBalance is not a function of EEI revision 3"
+        );
+    }
+
+    #[test]
+    fn best_match_without_compatible_revision() {
+        let spec = InterfaceSpec::parse(
+            "revision 1\nnamespace ethereum\nfinish(i64)\n\
+             revision 2\nnamespace ethereum\nchange finish(i32, i32) -> i32",
+        )
+        .unwrap();
+        let report = RevisionReport::detect_with(&module(GET_BALANCE_AND_FINISH), &spec);
+        assert!(report.compatible().is_empty());
+        assert_eq!(report.best_match(), Some(2));
+        assert_eq!(report.conflicts(1).len(), 2);
+        assert!(report.to_string().contains("(incompatible)"));
+    }
+}
@@ -0,0 +1,700 @@
+/*
+ * libeci: Ethereum WebAssembly ABI compliance library
+ *
+ * Copyright (c) 2018 Jake Lang
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+use eei::{Deprecation, FunctionDecl, ImportInterfaceMap, InterfaceChange, ParamSemantics};
+use parity_wasm::elements::{FunctionType, ValueType};
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::OnceLock;
+
+/// The interface spec of the ewasm EEI shipped with libeci.
+pub const EWASM_EEI_SPEC: &str = include_str!("../spec/ewasm-eei.txt");
+
+/// The kinds of problems found when parsing an interface spec.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SpecErrorKind {
+    /// The line could not be parsed.
+    Syntax(String),
+    /// A value type other than i32, i64, f32 or f64 was used.
+    UnknownValueType(String),
+    /// A function or parameter was declared more than once.
+    Duplicate(String),
+    /// A function was changed or removed without being declared.
+    UnknownFunction(String),
+    /// A revision was not greater than the one before it.
+    RevisionOrder(u32),
+    /// A function was declared before any revision.
+    MissingRevision,
+    /// A function was declared before any namespace.
+    MissingNamespace,
+}
+
+/// A problem found when parsing an interface spec, along with the line on which it occurs.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpecError {
+    pub line: usize,
+    pub kind: SpecErrorKind,
+}
+
+impl fmt::Display for SpecError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: ", self.line)?;
+        match self.kind {
+            SpecErrorKind::Syntax(ref msg) => write!(f, "{}", msg),
+            SpecErrorKind::UnknownValueType(ref ty) => write!(f, "unknown value type \"{}\"", ty),
+            SpecErrorKind::Duplicate(ref name) => write!(f, "duplicate declaration of {}", name),
+            SpecErrorKind::UnknownFunction(ref name) => write!(f, "unknown function {}", name),
+            SpecErrorKind::RevisionOrder(rev) => {
+                write!(f, "revision {} does not follow the previous revision", rev)
+            }
+            SpecErrorKind::MissingRevision => write!(f, "declaration outside of a revision"),
+            SpecErrorKind::MissingNamespace => write!(f, "declaration outside of a namespace"),
+        }
+    }
+}
+
+/// A single change to the host interface, as declared in a spec.
+#[derive(Clone, Debug, PartialEq)]
+struct SpecChange {
+    revision: u32,
+    namespace: String,
+    name: String,
+    change: InterfaceChange,
+}
+
+/// The revision history of a host interface, parsed from a text spec.
+///
+/// A spec is a list of lines, where "#" begins a comment:
+///
+/// ```text
+/// revision 1
+/// namespace ethereum
+/// getGasLeft() -> i64
+/// storageStore(pathOffset: i32, valueOffset: i32)
+///
+/// revision 2
+/// namespace ethereum
+/// change getGasLeft() -> i32
+/// remove storageStore
+/// storageWrite(pathOffset: i32, valueOffset: i32)
+/// getGasRemaining() -> i64
+/// deprecate getGasLeft by getGasRemaining: getGasLeft truncates to 32 bits
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct InterfaceSpec {
+    changes: Vec<SpecChange>,
+}
+
+impl InterfaceSpec {
+    /// Parses and validates an interface spec.
+    pub fn parse(text: &str) -> Result<Self, SpecError> {
+        let mut changes: Vec<SpecChange> = Vec::new();
+        let mut revision: Option<u32> = None;
+        let mut namespace: Option<String> = None;
+        let mut live: HashSet<String> = HashSet::new();
+        let mut mentioned: HashSet<String> = HashSet::new();
+        let mut deprecated: HashSet<String> = HashSet::new();
+
+        for (i, raw) in text.lines().enumerate() {
+            let line = i + 1;
+            let error = |kind| Err(SpecError { line, kind });
+            let content = raw.split('#').next().unwrap().trim();
+            if content.is_empty() {
+                continue;
+            }
+
+            let (keyword, rest) = match content.find(char::is_whitespace) {
+                Some(idx) => (&content[..idx], content[idx..].trim()),
+                None => (content, ""),
+            };
+
+            match keyword {
+                "revision" => {
+                    let rev: u32 = match rest.parse() {
+                        Ok(rev) => rev,
+                        Err(_) => {
+                            return error(SpecErrorKind::Syntax(format!(
+                                "invalid revision \"{}\"",
+                                rest
+                            )))
+                        }
+                    };
+                    if rev == 0 || revision.is_some_and(|prev| rev <= prev) {
+                        return error(SpecErrorKind::RevisionOrder(rev));
+                    }
+                    revision = Some(rev);
+                    mentioned.clear();
+                    continue;
+                }
+                "namespace" => {
+                    if !is_identifier(rest) {
+                        return error(SpecErrorKind::Syntax(format!(
+                            "invalid namespace \"{}\"",
+                            rest
+                        )));
+                    }
+                    namespace = Some(rest.to_string());
+                    continue;
+                }
+                _ => (),
+            }
+
+            let rev = match revision {
+                Some(rev) => rev,
+                None => return error(SpecErrorKind::MissingRevision),
+            };
+            let ns = match namespace {
+                Some(ref ns) => ns.clone(),
+                None => return error(SpecErrorKind::MissingNamespace),
+            };
+
+            let (name, change) = match keyword {
+                "remove" => {
+                    if !is_identifier(rest) {
+                        return error(SpecErrorKind::Syntax(format!(
+                            "invalid function name \"{}\"",
+                            rest
+                        )));
+                    }
+                    (rest.to_string(), InterfaceChange::Removed)
+                }
+                "deprecate" => {
+                    let (name, deprecation) =
+                        parse_deprecation(rest, rev).map_err(|kind| SpecError { line, kind })?;
+                    (name, InterfaceChange::Deprecated(deprecation))
+                }
+                "change" => {
+                    let (name, decl) =
+                        parse_declaration(rest).map_err(|kind| SpecError { line, kind })?;
+                    (name, InterfaceChange::Changed(decl))
+                }
+                _ => {
+                    let (name, decl) =
+                        parse_declaration(content).map_err(|kind| SpecError { line, kind })?;
+                    (name, InterfaceChange::Added(decl))
+                }
+            };
+
+            let qualified = format!("{}.{}", ns, name);
+            let is_deprecation = matches!(change, InterfaceChange::Deprecated(_));
+            if !is_deprecation && !mentioned.insert(qualified.clone()) {
+                return error(SpecErrorKind::Duplicate(qualified));
+            }
+            match change {
+                InterfaceChange::Added(_) => {
+                    if !live.insert(qualified.clone()) {
+                        return error(SpecErrorKind::Duplicate(qualified));
+                    }
+                }
+                InterfaceChange::Changed(_) => {
+                    if !live.contains(&qualified) {
+                        return error(SpecErrorKind::UnknownFunction(qualified));
+                    }
+                }
+                InterfaceChange::Removed => {
+                    if !live.remove(&qualified) {
+                        return error(SpecErrorKind::UnknownFunction(qualified));
+                    }
+                    deprecated.remove(&qualified);
+                }
+                InterfaceChange::Deprecated(_) => {
+                    if !live.contains(&qualified) {
+                        return error(SpecErrorKind::UnknownFunction(qualified));
+                    }
+                    if !deprecated.insert(qualified.clone()) {
+                        return error(SpecErrorKind::Duplicate(format!(
+                            "deprecation of {}",
+                            qualified
+                        )));
+                    }
+                }
+            }
+
+            changes.push(SpecChange {
+                revision: rev,
+                namespace: ns,
+                name,
+                change,
+            });
+        }
+
+        Ok(InterfaceSpec { changes })
+    }
+
+    /// Returns the parsed spec of the ewasm EEI shipped with libeci, which is parsed only once.
+    pub fn builtin() -> &'static Self {
+        static BUILTIN: OnceLock<InterfaceSpec> = OnceLock::new();
+        BUILTIN.get_or_init(|| {
+            InterfaceSpec::parse(EWASM_EEI_SPEC).expect("built-in EEI spec is valid")
+        })
+    }
+
+    /// Returns the latest revision declared by the spec.
+    pub fn latest_revision(&self) -> u32 {
+        self.changes.last().map(|c| c.revision).unwrap_or(0)
+    }
+
+    /// Returns every revision declared by the spec, in ascending order.
+    pub fn revisions(&self) -> Vec<u32> {
+        let mut revisions: Vec<u32> = self.changes.iter().map(|c| c.revision).collect();
+        revisions.dedup();
+        revisions
+    }
+
+    /// Constructs the interface map of the given revision. Revisions newer than the latest one
+    /// declared are treated as the latest.
+    pub fn build(&self, rev: u32) -> ImportInterfaceMap {
+        let mut map = ImportInterfaceMap::new();
+
+        for c in self.changes.iter().take_while(|c| c.revision <= rev) {
+            map.apply(c.revision, &c.namespace, &c.name, c.change.clone());
+        }
+        map.set_revision_number(rev.min(self.latest_revision()));
+        map
+    }
+}
+
+/// Determines whether a string is a valid function, parameter or namespace name.
+pub(crate) fn is_identifier(s: &str) -> bool {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+            chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        }
+        _ => false,
+    }
+}
+
+/// Parses a value type name.
+fn parse_value_type(s: &str) -> Result<ValueType, SpecErrorKind> {
+    match s {
+        "i32" => Ok(ValueType::I32),
+        "i64" => Ok(ValueType::I64),
+        "f32" => Ok(ValueType::F32),
+        "f64" => Ok(ValueType::F64),
+        _ => Err(SpecErrorKind::UnknownValueType(s.to_string())),
+    }
+}
+
+/// Parses a deprecation of the form "name [by replacement] [: note]".
+fn parse_deprecation(s: &str, since: u32) -> Result<(String, Deprecation), SpecErrorKind> {
+    let (head, note) = match s.find(':') {
+        Some(idx) => (&s[..idx], Some(s[idx + 1..].trim().to_string())),
+        None => (s, None),
+    };
+    let words: Vec<&str> = head.split_whitespace().collect();
+
+    let (name, replacement) = match words.as_slice() {
+        [name] => (*name, None),
+        [name, "by", replacement] if is_identifier(replacement) => {
+            (*name, Some(replacement.to_string()))
+        }
+        _ => {
+            return Err(SpecErrorKind::Syntax(format!(
+                "expected \"name [by replacement] [: note]\" in \"{}\"",
+                s
+            )))
+        }
+    };
+    if !is_identifier(name) {
+        return Err(SpecErrorKind::Syntax(format!(
+            "invalid function name \"{}\"",
+            name
+        )));
+    }
+
+    Ok((
+        name.to_string(),
+        Deprecation {
+            since,
+            replacement,
+            note: note.filter(|note| !note.is_empty()),
+        },
+    ))
+}
+
+/// Parses a parameter annotation: "gas", "length", "in" or "out" followed by a fixed length or
+/// the name of a length parameter in brackets, e.g. "out[20]" or "in[dataLength]", or "count"
+/// followed by a range of parameter names in brackets, e.g. "count[topic1..topic4]".
+fn parse_semantics(s: &str, names: &[String]) -> Result<ParamSemantics, SpecErrorKind> {
+    let syntax = || {
+        Err(SpecErrorKind::Syntax(format!(
+            "invalid parameter annotation \"{}\"",
+            s
+        )))
+    };
+
+    let (direction, len) = match s {
+        "gas" => return Ok(ParamSemantics::Gas),
+        "length" => return Ok(ParamSemantics::Length),
+        _ => match (s.find('['), s.strip_suffix(']')) {
+            (Some(idx), Some(head)) => (&s[..idx], &head[idx + 1..]),
+            _ => return syntax(),
+        },
+    };
+
+    let position = |name: &str| match names.iter().position(|n| n == name) {
+        Some(index) => Ok(index),
+        None => Err(SpecErrorKind::Syntax(format!(
+            "unknown parameter \"{}\" in \"{}\"",
+            name, s
+        ))),
+    };
+
+    if direction == "count" {
+        let (first, last) = match len.find("..") {
+            Some(idx) => (position(&len[..idx])?, position(&len[idx + 2..])?),
+            None => return syntax(),
+        };
+        if first > last {
+            return syntax();
+        }
+        return Ok(ParamSemantics::Count(first, last));
+    }
+
+    match (direction, len.parse::<u32>()) {
+        ("in", Ok(len)) => Ok(ParamSemantics::InPtr(len)),
+        ("out", Ok(len)) => Ok(ParamSemantics::OutPtr(len)),
+        ("in", Err(_)) | ("out", Err(_)) => {
+            let index = match names.iter().position(|n| n == len) {
+                Some(index) => index,
+                None => {
+                    return Err(SpecErrorKind::Syntax(format!(
+                        "unknown length parameter \"{}\"",
+                        len
+                    )))
+                }
+            };
+            Ok(if direction == "in" {
+                ParamSemantics::InPtrLen(index)
+            } else {
+                ParamSemantics::OutPtrLen(index)
+            })
+        }
+        _ => syntax(),
+    }
+}
+
+/// Parses a declaration of the form "name(param: type annotation, ...) -> type".
+fn parse_declaration(s: &str) -> Result<(String, FunctionDecl), SpecErrorKind> {
+    let syntax = |msg: &str| Err(SpecErrorKind::Syntax(format!("{} in \"{}\"", msg, s)));
+
+    let (name, rest) = match s.find('(') {
+        Some(idx) => (s[..idx].trim(), &s[idx + 1..]),
+        None => return syntax("expected a parameter list"),
+    };
+    if !is_identifier(name) {
+        return syntax("invalid function name");
+    }
+    let (params, rest) = match rest.find(')') {
+        Some(idx) => (rest[..idx].trim(), rest[idx + 1..].trim()),
+        None => return syntax("unterminated parameter list"),
+    };
+
+    let mut types: Vec<ValueType> = Vec::new();
+    let mut names: Vec<String> = Vec::new();
+    let mut annotations: Vec<Option<&str>> = Vec::new();
+    if !params.is_empty() {
+        for param in params.split(',') {
+            let param = param.trim();
+            let ty = match param.find(':') {
+                Some(idx) => {
+                    let param_name = param[..idx].trim();
+                    if !is_identifier(param_name) {
+                        return syntax("invalid parameter name");
+                    }
+                    if names.iter().any(|n| n == param_name) {
+                        return Err(SpecErrorKind::Duplicate(format!(
+                            "parameter {} of {}",
+                            param_name, name
+                        )));
+                    }
+                    names.push(param_name.to_string());
+                    &param[idx + 1..]
+                }
+                None => param,
+            };
+
+            let words: Vec<&str> = ty.split_whitespace().collect();
+            let (ty, annotation) = match words.as_slice() {
+                [ty] => (*ty, None),
+                [ty, annotation] => (*ty, Some(*annotation)),
+                _ => return syntax("expected a type and an optional annotation"),
+            };
+            types.push(parse_value_type(ty)?);
+            annotations.push(annotation);
+        }
+        if !names.is_empty() && names.len() != types.len() {
+            return syntax("either all or no parameters must be named");
+        }
+    }
+
+    let semantics: Vec<ParamSemantics> = if annotations.iter().all(|a| a.is_none()) {
+        Vec::new()
+    } else {
+        annotations
+            .iter()
+            .map(|annotation| match *annotation {
+                Some(annotation) => parse_semantics(annotation, &names),
+                None => Ok(ParamSemantics::Value),
+            })
+            .collect::<Result<_, _>>()?
+    };
+
+    let result = if rest.is_empty() {
+        None
+    } else if let Some(ty) = rest.strip_prefix("->") {
+        Some(parse_value_type(ty.trim())?)
+    } else {
+        return syntax("expected \"->\" or end of line");
+    };
+
+    Ok((
+        name.to_string(),
+        FunctionDecl {
+            signature: FunctionType::new(types, result),
+            param_names: names,
+            semantics,
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use eei::{Deprecation, ParamSemantics, EEI_REV};
+    use parity_wasm::elements::{FunctionType, ValueType};
+    use spec::*;
+
+    fn kind_and_line(text: &str) -> (SpecErrorKind, usize) {
+        let err = InterfaceSpec::parse(text).unwrap_err();
+        (err.kind, err.line)
+    }
+
+    #[test]
+    fn builtin_spec() {
+        let spec = InterfaceSpec::builtin();
+        assert_eq!(spec.latest_revision(), EEI_REV);
+        let iface = spec.build(EEI_REV);
+        let call = iface.get_host_function("ethereum", "call").unwrap();
+        assert_eq!(call.namespace(), "ethereum");
+        assert_eq!(call.param_name(0), Some("gas"));
+        assert_eq!(call.param_name(5), None);
+    }
+
+    #[test]
+    fn parse_small_spec() {
+        let text = "
+            # A comment.
+            revision 1
+            namespace env
+            foo(i32, i64) -> f64   # trailing comment
+            bar(a: i32)
+
+            revision 4
+            namespace env
+            change foo() -> i32
+            remove bar
+        ";
+        let spec = InterfaceSpec::parse(text).unwrap();
+        assert_eq!(spec.latest_revision(), 4);
+        assert_eq!(spec.revisions(), vec![1, 4]);
+
+        let first = spec.build(1);
+        assert_eq!(
+            first.lookup_func("env", "foo").unwrap(),
+            FunctionType::new(vec![ValueType::I32, ValueType::I64], Some(ValueType::F64))
+        );
+        assert_eq!(
+            first.get_host_function("env", "bar").unwrap().param_name(0),
+            Some("a")
+        );
+
+        let latest = spec.build(10);
+        assert_eq!(latest.revision_number(), 4);
+        assert_eq!(
+            latest.lookup_func("env", "foo").unwrap(),
+            FunctionType::new(Vec::new(), Some(ValueType::I32))
+        );
+        assert_eq!(latest.removed_in("env", "bar"), Some(4));
+    }
+
+    #[test]
+    fn unknown_value_type() {
+        assert_eq!(
+            kind_and_line("revision 1\nnamespace env\nfoo(i32, u8)"),
+            (SpecErrorKind::UnknownValueType("u8".to_string()), 3)
+        );
+        assert_eq!(
+            kind_and_line("revision 1\nnamespace env\nfoo() -> v128"),
+            (SpecErrorKind::UnknownValueType("v128".to_string()), 3)
+        );
+    }
+
+    #[test]
+    fn duplicates() {
+        assert_eq!(
+            kind_and_line("revision 1\nnamespace env\nfoo()\nfoo(i32)"),
+            (SpecErrorKind::Duplicate("env.foo".to_string()), 4)
+        );
+        assert_eq!(
+            kind_and_line("revision 1\nnamespace env\nfoo()\nrevision 2\nfoo()"),
+            (SpecErrorKind::Duplicate("env.foo".to_string()), 5)
+        );
+        assert_eq!(
+            kind_and_line("revision 1\nnamespace env\nfoo(a: i32, a: i32)"),
+            (
+                SpecErrorKind::Duplicate("parameter a of foo".to_string()),
+                3
+            )
+        );
+    }
+
+    #[test]
+    fn structural_errors() {
+        assert_eq!(
+            kind_and_line("namespace env\nfoo()"),
+            (SpecErrorKind::MissingRevision, 2)
+        );
+        assert_eq!(
+            kind_and_line("revision 1\nfoo()"),
+            (SpecErrorKind::MissingNamespace, 2)
+        );
+        assert_eq!(
+            kind_and_line("revision 2\nrevision 2"),
+            (SpecErrorKind::RevisionOrder(2), 2)
+        );
+        assert_eq!(
+            kind_and_line("revision 1\nnamespace env\nremove foo"),
+            (SpecErrorKind::UnknownFunction("env.foo".to_string()), 3)
+        );
+        match kind_and_line("revision 1\nnamespace env\nfoo(a: i32, i32)").0 {
+            SpecErrorKind::Syntax(_) => (),
+            kind => panic!("unexpected error {:?}", kind),
+        }
+        match kind_and_line("revision 1\nnamespace env\nfoo(i32").0 {
+            SpecErrorKind::Syntax(_) => (),
+            kind => panic!("unexpected error {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn error_display() {
+        let err = InterfaceSpec::parse("revision 1\nnamespace env\nfoo(u8)").unwrap_err();
+        assert_eq!(err.to_string(), "line 3: unknown value type \"u8\"");
+    }
+
+    #[test]
+    fn deprecations() {
+        let text = "
+            revision 1
+            namespace env
+            foo()
+            bar()
+            revision 2
+            namespace env
+            deprecate foo by bar: use bar instead
+            deprecate bar
+        ";
+        let spec = InterfaceSpec::parse(text).unwrap();
+        assert!(spec
+            .build(1)
+            .get_host_function("env", "foo")
+            .unwrap()
+            .deprecation()
+            .is_none());
+
+        let iface = spec.build(2);
+        let foo = iface.get_host_function("env", "foo").unwrap();
+        assert_eq!(
+            foo.deprecation(),
+            Some(&Deprecation {
+                since: 2,
+                replacement: Some("bar".to_string()),
+                note: Some("use bar instead".to_string()),
+            })
+        );
+        let bar = iface.get_host_function("env", "bar").unwrap();
+        assert_eq!(bar.deprecation().unwrap().replacement, None);
+        assert_eq!(bar.deprecation().unwrap().note, None);
+
+        assert_eq!(
+            kind_and_line("revision 1\nnamespace env\ndeprecate foo"),
+            (SpecErrorKind::UnknownFunction("env.foo".to_string()), 3)
+        );
+        assert_eq!(
+            kind_and_line("revision 1\nnamespace env\nfoo()\ndeprecate foo\ndeprecate foo"),
+            (
+                SpecErrorKind::Duplicate("deprecation of env.foo".to_string()),
+                5
+            )
+        );
+        match kind_and_line("revision 1\nnamespace env\nfoo()\ndeprecate foo with bar").0 {
+            SpecErrorKind::Syntax(_) => (),
+            kind => panic!("unexpected error {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn parameter_annotations() {
+        let text = "revision 1\nnamespace env\n\
+                    copy(gas: i64 gas, src: i32 in[len], dst: i32 out[32], len: i32 length)";
+        let iface = InterfaceSpec::parse(text).unwrap().build(1);
+        let copy = iface.get_host_function("env", "copy").unwrap();
+        assert_eq!(copy.param_semantics(0), ParamSemantics::Gas);
+        assert_eq!(copy.param_semantics(1), ParamSemantics::InPtrLen(3));
+        assert_eq!(copy.param_semantics(2), ParamSemantics::OutPtr(32));
+        assert_eq!(copy.param_semantics(3), ParamSemantics::Length);
+        assert_eq!(copy.param_semantics(4), ParamSemantics::Value);
+
+        let iface = InterfaceSpec::parse("revision 1\nnamespace env\nfoo(i32 in[4], i32)")
+            .unwrap()
+            .build(1);
+        let foo = iface.get_host_function("env", "foo").unwrap();
+        assert_eq!(foo.param_semantics(0), ParamSemantics::InPtr(4));
+        assert_eq!(foo.param_semantics(1), ParamSemantics::Value);
+
+        let iface = InterfaceSpec::parse(
+            "revision 1\nnamespace env\nemit(n: i32 count[a..b], a: i32 in[1], b: i32 in[1])",
+        )
+        .unwrap()
+        .build(1);
+        let emit = iface.get_host_function("env", "emit").unwrap();
+        assert_eq!(emit.param_semantics(0), ParamSemantics::Count(1, 2));
+
+        for bad in &[
+            "foo(a: i32 in[b])",
+            "foo(a: i32 sideways[4])",
+            "foo(a: i32 out[4)",
+            "foo(a: i32 gas length)",
+            "foo(a: i32 count[b..c], b: i32, c: i32 count[a])",
+            "foo(a: i32 count[c..b], b: i32, c: i32)",
+            "foo(a: i32 count[b..d], b: i32, c: i32)",
+        ] {
+            match kind_and_line(&format!("revision 1\nnamespace env\n{}", bad)).0 {
+                SpecErrorKind::Syntax(_) => (),
+                kind => panic!("unexpected error {:?} for {}", kind, bad),
+            }
+        }
+    }
+}
@@ -0,0 +1,249 @@
+/*
+ * libeci: Ethereum WebAssembly ABI compliance library
+ *
+ * Copyright (c) 2018 Jake Lang
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+use checker::prepare;
+use checklist::EciChecklist;
+use eci_std::chk_stack_limit;
+use parity_wasm::elements::{
+    BlockType, GlobalEntry, GlobalType, InitExpr, Instruction, Module, ValueType,
+};
+use pwasm::callable_func_type;
+use transform::{add_global, finish, TransformError, Transformed};
+
+/// A module's stack height instrumentation, as found by `detect_stack_limit`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StackLimit {
+    /// The index of the global holding the current stack height.
+    pub global: u32,
+    /// The stack height above which a call traps.
+    pub limit: u32,
+    /// The callable indices of functions which do not update the stack height.
+    pub uninstrumented: Vec<u32>,
+}
+
+/// Returns the amount a call to a function adds to the stack height: one for the frame itself,
+/// plus one for each parameter and local. Returns None if the function is not defined by the
+/// module or its type does not exist.
+pub fn frame_cost(module: &Module, index: u32) -> Option<u32> {
+    let import_len = module.import_section().map_or(0, |i| i.functions());
+    let params = callable_func_type(module, index)?.params().len() as u32;
+    let body = module
+        .code_section()?
+        .bodies()
+        .get((index as usize).checked_sub(import_len)?)?;
+    let locals: u32 = body.locals().iter().map(|local| local.count()).sum();
+    Some(1 + params + locals)
+}
+
+/// Returns the instructions which raise the stack height on entry to a function and trap if it
+/// exceeds the limit.
+fn prologue(global: u32, cost: u32, limit: u32) -> Vec<Instruction> {
+    vec![
+        Instruction::GetGlobal(global),
+        Instruction::I32Const(cost as i32),
+        Instruction::I32Add,
+        Instruction::SetGlobal(global),
+        Instruction::GetGlobal(global),
+        Instruction::I32Const(limit as i32),
+        Instruction::I32GtU,
+        Instruction::If(BlockType::NoResult),
+        Instruction::Unreachable,
+        Instruction::End,
+    ]
+}
+
+/// Returns the instructions which lower the stack height on exit from a function.
+fn epilogue(global: u32, cost: u32) -> Vec<Instruction> {
+    vec![
+        Instruction::GetGlobal(global),
+        Instruction::I32Const(cost as i32),
+        Instruction::I32Sub,
+        Instruction::SetGlobal(global),
+    ]
+}
+
+/// Returns the global and limit a function body's prologue updates, if it has one.
+fn instrumented_by(code: &[Instruction]) -> Option<(u32, u32)> {
+    match code {
+        [Instruction::GetGlobal(g1), Instruction::I32Const(_), Instruction::I32Add, Instruction::SetGlobal(g2), Instruction::GetGlobal(g3), Instruction::I32Const(limit), Instruction::I32GtU, Instruction::If(BlockType::NoResult), Instruction::Unreachable, Instruction::End, ..]
+            if g1 == g2 && g2 == g3 =>
+        {
+            Some((*g1, *limit as u32))
+        }
+        _ => None,
+    }
+}
+
+/// Wraps a function body in a block between a prologue and an epilogue. Branches to the function
+/// body's label now target the wrapping block, and returns are rewritten into such branches, so
+/// that every normal exit passes through the epilogue.
+fn instrument(
+    code: &[Instruction],
+    result: BlockType,
+    global: u32,
+    cost: u32,
+    limit: u32,
+) -> Vec<Instruction> {
+    let mut out = prologue(global, cost, limit);
+    out.push(Instruction::Block(result));
+
+    let mut depth = 0;
+    for instr in code {
+        match *instr {
+            Instruction::Block(_) | Instruction::Loop(_) | Instruction::If(_) => {
+                depth += 1;
+                out.push(instr.clone());
+            }
+            Instruction::End if depth == 0 => {
+                out.push(Instruction::End);
+                out.extend(epilogue(global, cost));
+                out.push(Instruction::End);
+            }
+            Instruction::End => {
+                depth -= 1;
+                out.push(Instruction::End);
+            }
+            Instruction::Return => out.push(Instruction::Br(depth)),
+            _ => out.push(instr.clone()),
+        }
+    }
+    out
+}
+
+/// Finds the stack height instrumentation of a module, if any of its functions is instrumented.
+pub fn detect_stack_limit(module: &Module) -> Option<StackLimit> {
+    let import_len = module.import_section().map_or(0, |i| i.functions());
+    let bodies = module.code_section().map_or(&[][..], |code| code.bodies());
+    let found: Vec<Option<(u32, u32)>> = bodies
+        .iter()
+        .map(|body| instrumented_by(body.code().elements()))
+        .collect();
+    let (global, limit) = found.iter().filter_map(|f| *f).next()?;
+
+    Some(StackLimit {
+        global,
+        limit,
+        uninstrumented: found
+            .iter()
+            .enumerate()
+            .filter(|(_, f)| **f != Some((global, limit)))
+            .map(|(i, _)| (import_len + i) as u32)
+            .collect(),
+    })
+}
+
+/// Rewrites a module so that every function it defines adds its frame cost to a new global on
+/// entry, traps if the total exceeds the limit, and subtracts it again on exit. Stack limiting
+/// should be applied before gas metering, so that the added instructions are metered too. The
+/// rewritten module is checked against the checklist with the stack limit check added.
+pub fn limit_stack_height(
+    code: &[u8],
+    limit: u32,
+    checklist: &EciChecklist,
+) -> Result<Transformed, TransformError> {
+    let mut module = prepare(code, checklist)?;
+    if detect_stack_limit(&module).is_some() {
+        return Err(TransformError::Conflict(
+            "module already limits its stack height".to_string(),
+        ));
+    }
+
+    let import_len = module.import_section().map_or(0, |i| i.functions());
+    let body_len = module.code_section().map_or(0, |code| code.bodies().len());
+    let frames: Vec<(u32, BlockType)> = (0..body_len)
+        .map(|i| {
+            let index = (import_len + i) as u32;
+            let invalid = || {
+                TransformError::Invalid(format!(
+                    "function {} refers to a type which does not exist",
+                    index
+                ))
+            };
+            let sig = callable_func_type(&module, index).ok_or_else(invalid)?;
+            let result = match sig.return_type() {
+                Some(ty) => BlockType::Value(ty),
+                None => BlockType::NoResult,
+            };
+            Ok((frame_cost(&module, index).ok_or_else(invalid)?, result))
+        })
+        .collect::<Result<_, TransformError>>()?;
+
+    let global = add_global(
+        &mut module,
+        GlobalEntry::new(
+            GlobalType::new(ValueType::I32, true),
+            InitExpr::new(vec![Instruction::I32Const(0), Instruction::End]),
+        ),
+    );
+    if let Some(code) = module.code_section_mut() {
+        for (body, &(cost, result)) in code.bodies_mut().iter_mut().zip(frames.iter()) {
+            let instrs = body.code_mut().elements_mut();
+            *instrs = instrument(instrs, result, global, cost, limit);
+        }
+    }
+
+    let mut checklist = checklist.clone();
+    checklist.add_detailed_check("stack-limit", chk_stack_limit);
+    finish(module, code, &checklist)
+}
+
+#[cfg(test)]
+mod tests {
+    use parity_wasm::elements::Instruction::*;
+    use stack_height::*;
+
+    #[test]
+    fn returns_become_branches() {
+        let code = [
+            GetLocal(0),
+            If(BlockType::NoResult),
+            I32Const(1),
+            Return,
+            End,
+            I32Const(2),
+            End,
+        ];
+        let out = instrument(&code, BlockType::Value(ValueType::I32), 0, 2, 100);
+        assert_eq!(instrumented_by(&out), Some((0, 100)));
+        assert_eq!(
+            &out[10..],
+            &[
+                Block(BlockType::Value(ValueType::I32)),
+                GetLocal(0),
+                If(BlockType::NoResult),
+                I32Const(1),
+                Br(1),
+                End,
+                I32Const(2),
+                End,
+                GetGlobal(0),
+                I32Const(2),
+                I32Sub,
+                SetGlobal(0),
+                End,
+            ]
+        );
+        assert_eq!(instrumented_by(&code), None);
+    }
+}
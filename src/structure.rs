@@ -0,0 +1,313 @@
+/*
+ * libeci: Ethereum WebAssembly ABI compliance library
+ *
+ * Copyright (c) 2018 Jake Lang
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+pub use binary::LebError;
+
+use binary::read_leb;
+use std::fmt;
+
+/// The magic number which begins every WASM module.
+pub const WASM_MAGIC: [u8; 4] = [0x00, 0x61, 0x73, 0x6d];
+
+/// The only WASM binary format version supported.
+pub const WASM_VERSION: u32 = 1;
+
+/// The highest known section id.
+const MAX_SECTION_ID: u8 = 11;
+
+/// The kinds of structural problems found by the pre-scan.
+#[derive(Clone, Debug, PartialEq)]
+pub enum StructureErrorKind {
+    /// The input ends before the module header is complete.
+    UnexpectedEnd,
+    /// The input does not begin with the WASM magic number.
+    BadMagic,
+    /// The module declares an unsupported binary format version.
+    UnsupportedVersion(u32),
+    /// A section has an id which is not defined by the binary format.
+    InvalidSectionId(u8),
+    /// A section appears after a section which must follow it.
+    SectionOutOfOrder { id: u8, previous: u8 },
+    /// A non-custom section appears more than once.
+    DuplicateSection(u8),
+    /// A section's size is not a valid unsigned 32-bit LEB128 integer.
+    MalformedSectionSize,
+    /// A section's size runs past the end of the input.
+    SectionOverrun { id: u8, size: u32, remaining: usize },
+    /// The input ends with bytes which do not form a complete section.
+    TrailingGarbage,
+    /// The contents of a section cannot be decoded.
+    MalformedSectionContents(u8),
+}
+
+/// A structural problem found by the pre-scan, along with the byte offset at which it occurs.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StructureError {
+    pub kind: StructureErrorKind,
+    pub offset: usize,
+}
+
+impl fmt::Display for StructureError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            StructureErrorKind::UnexpectedEnd => write!(f, "unexpected end of module header"),
+            StructureErrorKind::BadMagic => write!(f, "bad magic number"),
+            StructureErrorKind::UnsupportedVersion(version) => {
+                write!(f, "unsupported version {}", version)
+            }
+            StructureErrorKind::InvalidSectionId(id) => write!(f, "invalid section id {}", id),
+            StructureErrorKind::SectionOutOfOrder { id, previous } => {
+                write!(f, "section {} out of order after section {}", id, previous)
+            }
+            StructureErrorKind::DuplicateSection(id) => write!(f, "duplicate section {}", id),
+            StructureErrorKind::MalformedSectionSize => write!(f, "malformed section size"),
+            StructureErrorKind::SectionOverrun {
+                id,
+                size,
+                remaining,
+            } => write!(
+                f,
+                "section {} of size {} overruns the {} remaining bytes",
+                id, size, remaining
+            ),
+            StructureErrorKind::TrailingGarbage => write!(f, "trailing garbage"),
+            StructureErrorKind::MalformedSectionContents(id) => {
+                write!(f, "malformed contents of section {}", id)
+            }
+        }?;
+        write!(f, " at byte {}", self.offset)
+    }
+}
+
+/// Location of a section within the raw bytecode.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SectionHeader {
+    /// The section id.
+    pub id: u8,
+    /// Offset of the section id byte.
+    pub offset: usize,
+    /// Offset of the first byte of the section's payload.
+    pub payload_offset: usize,
+    /// Length of the section's payload in bytes.
+    pub size: usize,
+}
+
+/// Scans the raw bytecode for header and section framing problems without deserializing it.
+/// Returns the location of every section if the structure is sound.
+pub fn scan(code: &[u8]) -> Result<Vec<SectionHeader>, StructureError> {
+    let error = |kind, offset| Err(StructureError { kind, offset });
+
+    if code.len() < 4 {
+        return if WASM_MAGIC.starts_with(code) {
+            error(StructureErrorKind::UnexpectedEnd, code.len())
+        } else {
+            error(StructureErrorKind::BadMagic, 0)
+        };
+    }
+    if code[0..4] != WASM_MAGIC {
+        return error(StructureErrorKind::BadMagic, 0);
+    }
+    if code.len() < 8 {
+        return error(StructureErrorKind::UnexpectedEnd, code.len());
+    }
+    let version = u32::from(code[4])
+        | (u32::from(code[5]) << 8)
+        | (u32::from(code[6]) << 16)
+        | (u32::from(code[7]) << 24);
+    if version != WASM_VERSION {
+        return error(StructureErrorKind::UnsupportedVersion(version), 4);
+    }
+
+    let mut sections: Vec<SectionHeader> = Vec::new();
+    let mut previous: u8 = 0;
+    let mut offset: usize = 8;
+
+    while offset < code.len() {
+        let id = code[offset];
+        if id > MAX_SECTION_ID {
+            return error(StructureErrorKind::InvalidSectionId(id), offset);
+        }
+
+        let (size, size_len) = match read_varuint32(code, offset + 1) {
+            Ok(size) => size,
+            Err(LebError::UnexpectedEnd) => {
+                return error(StructureErrorKind::TrailingGarbage, offset)
+            }
+            Err(LebError::Overflow) => {
+                return error(StructureErrorKind::MalformedSectionSize, offset + 1)
+            }
+        };
+
+        if id != 0 {
+            if id == previous {
+                return error(StructureErrorKind::DuplicateSection(id), offset);
+            }
+            if id < previous {
+                return error(
+                    StructureErrorKind::SectionOutOfOrder { id, previous },
+                    offset,
+                );
+            }
+            previous = id;
+        }
+
+        let payload_offset = offset + 1 + size_len;
+        let remaining = code.len() - payload_offset;
+        if size as usize > remaining {
+            return error(
+                StructureErrorKind::SectionOverrun {
+                    id,
+                    size,
+                    remaining,
+                },
+                offset,
+            );
+        }
+
+        sections.push(SectionHeader {
+            id,
+            offset,
+            payload_offset,
+            size: size as usize,
+        });
+        offset = payload_offset + size as usize;
+    }
+
+    Ok(sections)
+}
+
+/// Reads an unsigned 32-bit LEB128 integer, returning its value and encoded length in bytes.
+pub fn read_varuint32(code: &[u8], offset: usize) -> Result<(u32, usize), LebError> {
+    read_leb(code, offset, false, 32).map(|(value, len)| (value as u32, len))
+}
+
+#[cfg(test)]
+mod tests {
+    use structure::*;
+
+    const HEADER: [u8; 8] = [0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
+
+    fn with_header(rest: &[u8]) -> Vec<u8> {
+        let mut code = HEADER.to_vec();
+        code.extend_from_slice(rest);
+        code
+    }
+
+    fn kind_and_offset(code: &[u8]) -> (StructureErrorKind, usize) {
+        let err = scan(code).unwrap_err();
+        (err.kind, err.offset)
+    }
+
+    #[test]
+    fn empty_module() {
+        assert!(scan(&HEADER).unwrap().is_empty());
+    }
+
+    #[test]
+    fn section_locations() {
+        let code = with_header(&[0x01, 0x04, 0x01, 0x60, 0x00, 0x00, 0x00, 0x01, 0x00]);
+        let sections = scan(&code).unwrap();
+        assert_eq!(sections.len(), 2);
+        assert_eq!(
+            sections[0],
+            SectionHeader {
+                id: 1,
+                offset: 8,
+                payload_offset: 10,
+                size: 4
+            }
+        );
+        assert_eq!(sections[1].id, 0);
+        assert_eq!(sections[1].offset, 14);
+    }
+
+    #[test]
+    fn bad_header() {
+        assert_eq!(
+            kind_and_offset(&[0x00, 0x77, 0x61, 0x73, 0x6d]),
+            (StructureErrorKind::BadMagic, 0)
+        );
+        assert_eq!(
+            kind_and_offset(&[0x00, 0x61]),
+            (StructureErrorKind::UnexpectedEnd, 2)
+        );
+        assert_eq!(
+            kind_and_offset(&[0x00, 0x61, 0x73, 0x6d, 0x01]),
+            (StructureErrorKind::UnexpectedEnd, 5)
+        );
+        assert_eq!(
+            kind_and_offset(&[0x00, 0x61, 0x73, 0x6d, 0x02, 0x00, 0x00, 0x00]),
+            (StructureErrorKind::UnsupportedVersion(2), 4)
+        );
+    }
+
+    #[test]
+    fn bad_sections() {
+        assert_eq!(
+            kind_and_offset(&with_header(&[0x0c, 0x00])),
+            (StructureErrorKind::InvalidSectionId(12), 8)
+        );
+        assert_eq!(
+            kind_and_offset(&with_header(&[0x03, 0x00, 0x01, 0x00])),
+            (
+                StructureErrorKind::SectionOutOfOrder { id: 1, previous: 3 },
+                10
+            )
+        );
+        assert_eq!(
+            kind_and_offset(&with_header(&[0x03, 0x00, 0x00, 0x00, 0x03, 0x00])),
+            (StructureErrorKind::DuplicateSection(3), 12)
+        );
+        assert_eq!(
+            kind_and_offset(&with_header(&[0x01, 0x05, 0x01, 0x60])),
+            (
+                StructureErrorKind::SectionOverrun {
+                    id: 1,
+                    size: 5,
+                    remaining: 2
+                },
+                8
+            )
+        );
+        assert_eq!(
+            kind_and_offset(&with_header(&[0x01, 0x80, 0x80, 0x80, 0x80, 0x10])),
+            (StructureErrorKind::MalformedSectionSize, 9)
+        );
+        assert_eq!(
+            kind_and_offset(&with_header(&[0x01, 0x00, 0x05])),
+            (StructureErrorKind::TrailingGarbage, 10)
+        );
+    }
+
+    #[test]
+    fn varuint32() {
+        assert_eq!(read_varuint32(&[0x05], 0), Ok((5, 1)));
+        assert_eq!(read_varuint32(&[0x85, 0x00], 0), Ok((5, 2)));
+        assert_eq!(
+            read_varuint32(&[0xff, 0xff, 0xff, 0xff, 0x0f], 0),
+            Ok((u32::MAX, 5))
+        );
+        assert_eq!(read_varuint32(&[0x80], 0), Err(LebError::UnexpectedEnd));
+    }
+}
@@ -0,0 +1,402 @@
+/*
+ * libeci: Ethereum WebAssembly ABI compliance library
+ *
+ * Copyright (c) 2018 Jake Lang
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+use checker::{check, ModuleError, Report};
+use checklist::EciChecklist;
+use parity_wasm::elements::{
+    External, FunctionType, GlobalEntry, GlobalSection, ImportCountType, ImportEntry,
+    ImportSection, Instruction, Internal, Module, Section, Type, TypeSection,
+};
+use parity_wasm::serialize;
+use std::collections::BTreeSet;
+use std::fmt;
+
+/// Errors preventing a module from being rewritten.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TransformError {
+    /// The input could not be decoded.
+    Module(ModuleError),
+    /// The rewritten module could not be encoded.
+    Serialize(String),
+    /// The module already contains something the transform would have to add.
+    Conflict(String),
+    /// The module refers to something which does not exist, so it cannot be renumbered safely.
+    Invalid(String),
+}
+
+impl fmt::Display for TransformError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TransformError::Module(ref err) => write!(f, "{}", err),
+            TransformError::Serialize(ref msg) => write!(f, "failed to serialize module: {}", msg),
+            TransformError::Conflict(ref msg) => write!(f, "cannot transform module: {}", msg),
+            TransformError::Invalid(ref msg) => write!(f, "invalid module: {}", msg),
+        }
+    }
+}
+
+impl From<ModuleError> for TransformError {
+    fn from(err: ModuleError) -> Self {
+        TransformError::Module(err)
+    }
+}
+
+/// A rewritten module and the report of checking it again.
+#[derive(Clone, Debug)]
+pub struct Transformed {
+    code: Vec<u8>,
+    original_len: usize,
+    report: Report,
+}
+
+impl Transformed {
+    /// Returns the bytecode of the rewritten module.
+    pub fn code(&self) -> &[u8] {
+        &self.code
+    }
+
+    /// Consumes the result, returning the bytecode of the rewritten module.
+    pub fn into_code(self) -> Vec<u8> {
+        self.code
+    }
+
+    /// Returns how many bytes smaller the rewritten module is than the original, which is negative
+    /// if the transform grew it.
+    pub fn bytes_saved(&self) -> isize {
+        self.original_len as isize - self.code.len() as isize
+    }
+
+    /// Returns the report of checking the rewritten module.
+    pub fn report(&self) -> &Report {
+        &self.report
+    }
+}
+
+/// Serializes a rewritten module and checks it against a checklist.
+pub(crate) fn finish(
+    module: Module,
+    original: &[u8],
+    checklist: &EciChecklist,
+) -> Result<Transformed, TransformError> {
+    let code = serialize(module).map_err(|e| TransformError::Serialize(e.to_string()))?;
+    let report = check(&code, checklist);
+    Ok(Transformed {
+        code,
+        original_len: original.len(),
+        report,
+    })
+}
+
+/// Returns the id of a known section, or None for custom sections.
+fn section_id(section: &Section) -> Option<u8> {
+    match *section {
+        Section::Type(_) => Some(1),
+        Section::Import(_) => Some(2),
+        Section::Function(_) => Some(3),
+        Section::Table(_) => Some(4),
+        Section::Memory(_) => Some(5),
+        Section::Global(_) => Some(6),
+        Section::Export(_) => Some(7),
+        Section::Start(_) => Some(8),
+        Section::Element(_) => Some(9),
+        Section::Code(_) => Some(10),
+        Section::Data(_) => Some(11),
+        Section::Unparsed { id, .. } if id != 0 => Some(id),
+        _ => None,
+    }
+}
+
+/// Inserts a section before the first known section which must follow it.
+fn insert_section(module: &mut Module, section: Section) {
+    let id = section_id(&section);
+    let sections = module.sections_mut();
+    let position = sections
+        .iter()
+        .position(|s| section_id(s).is_some_and(|other| Some(other) > id))
+        .unwrap_or(sections.len());
+    sections.insert(position, section);
+}
+
+/// Rewrites every reference to a function index: calls, exports, table elements and the start
+/// function. The name section is dropped, as its function names would no longer line up.
+pub fn remap_functions<F: Fn(u32) -> u32>(module: &mut Module, map: F) {
+    for section in module.sections_mut() {
+        match *section {
+            Section::Code(ref mut code) => {
+                for body in code.bodies_mut() {
+                    for instr in body.code_mut().elements_mut() {
+                        if let Instruction::Call(ref mut idx) = *instr {
+                            *idx = map(*idx);
+                        }
+                    }
+                }
+            }
+            Section::Export(ref mut exports) => {
+                for entry in exports.entries_mut() {
+                    if let Internal::Function(ref mut idx) = *entry.internal_mut() {
+                        *idx = map(*idx);
+                    }
+                }
+            }
+            Section::Element(ref mut elements) => {
+                for segment in elements.entries_mut() {
+                    for idx in segment.members_mut() {
+                        *idx = map(*idx);
+                    }
+                }
+            }
+            Section::Start(ref mut idx) => *idx = map(*idx),
+            _ => (),
+        }
+    }
+    module.sections_mut().retain(|section| match *section {
+        Section::Name(_) => false,
+        Section::Custom(ref custom) => custom.name() != "name",
+        _ => true,
+    });
+}
+
+/// Returns the index of a function type in the type section, adding it if it is missing.
+pub fn ensure_type(module: &mut Module, signature: &FunctionType) -> u32 {
+    if module.type_section().is_none() {
+        insert_section(module, Section::Type(TypeSection::with_types(Vec::new())));
+    }
+    let types = module
+        .type_section_mut()
+        .expect("type section exists")
+        .types_mut();
+    let existing = types.iter().position(|ty| match *ty {
+        Type::Function(ref func) => func == signature,
+    });
+    match existing {
+        Some(idx) => idx as u32,
+        None => {
+            types.push(Type::Function(signature.clone()));
+            (types.len() - 1) as u32
+        }
+    }
+}
+
+/// Appends a function import, shifting the indices of all functions defined by the module, and
+/// returns the callable index of the new import.
+pub fn add_function_import(
+    module: &mut Module,
+    namespace: &str,
+    field: &str,
+    signature: &FunctionType,
+) -> u32 {
+    let type_index = ensure_type(module, signature);
+    let index = module.import_section().map_or(0, |i| i.functions()) as u32;
+    remap_functions(module, |idx| if idx >= index { idx + 1 } else { idx });
+
+    if module.import_section().is_none() {
+        insert_section(
+            module,
+            Section::Import(ImportSection::with_entries(Vec::new())),
+        );
+    }
+    module
+        .import_section_mut()
+        .expect("import section exists")
+        .entries_mut()
+        .push(ImportEntry::new(
+            namespace.to_string(),
+            field.to_string(),
+            External::Function(type_index),
+        ));
+    index
+}
+
+/// Removes functions, imported or defined, and renumbers the rest. The removed functions must not
+/// be referenced by the remaining code, exports, table elements or start function.
+pub fn remove_functions(module: &mut Module, removed: &BTreeSet<u32>) {
+    if removed.is_empty() {
+        return;
+    }
+    let import_len = module.import_section().map_or(0, |i| i.functions()) as u32;
+
+    if let Some(imports) = module.import_section_mut() {
+        let mut index = 0;
+        imports
+            .entries_mut()
+            .retain(|entry| match *entry.external() {
+                External::Function(_) => {
+                    index += 1;
+                    !removed.contains(&(index - 1))
+                }
+                _ => true,
+            });
+    }
+    if let Some(functions) = module.function_section_mut() {
+        let mut index = import_len;
+        functions.entries_mut().retain(|_| {
+            index += 1;
+            !removed.contains(&(index - 1))
+        });
+    }
+    if let Some(code) = module.code_section_mut() {
+        let mut index = import_len;
+        code.bodies_mut().retain(|_| {
+            index += 1;
+            !removed.contains(&(index - 1))
+        });
+    }
+
+    remap_functions(module, |idx| idx - removed.range(..idx).count() as u32);
+    remove_empty_sections(module);
+}
+
+/// Removes function types which no import, function or `call_indirect` refers to, renumbering the
+/// rest, and returns how many were removed.
+pub fn remove_unused_types(module: &mut Module) -> usize {
+    let mut used: BTreeSet<u32> = BTreeSet::new();
+    for entry in module.import_section().map_or(&[][..], |i| i.entries()) {
+        if let External::Function(type_ref) = *entry.external() {
+            used.insert(type_ref);
+        }
+    }
+    for func in module.function_section().map_or(&[][..], |f| f.entries()) {
+        used.insert(func.type_ref());
+    }
+    for body in module.code_section().map_or(&[][..], |c| c.bodies()) {
+        for instr in body.code().elements() {
+            if let Instruction::CallIndirect(type_ref, _) = *instr {
+                used.insert(type_ref);
+            }
+        }
+    }
+
+    let type_len = module.type_section().map_or(0, |t| t.types().len()) as u32;
+    let unused: BTreeSet<u32> = (0..type_len).filter(|t| !used.contains(t)).collect();
+    if unused.is_empty() {
+        return 0;
+    }
+    let map = |idx: u32| idx - unused.range(..idx).count() as u32;
+
+    for section in module.sections_mut() {
+        match *section {
+            Section::Type(ref mut types) => {
+                let mut index = 0;
+                types.types_mut().retain(|_| {
+                    index += 1;
+                    !unused.contains(&(index - 1))
+                });
+            }
+            Section::Import(ref mut imports) => {
+                for entry in imports.entries_mut() {
+                    if let External::Function(ref mut type_ref) = *entry.external_mut() {
+                        *type_ref = map(*type_ref);
+                    }
+                }
+            }
+            Section::Function(ref mut functions) => {
+                for func in functions.entries_mut() {
+                    *func.type_ref_mut() = map(func.type_ref());
+                }
+            }
+            Section::Code(ref mut code) => {
+                for body in code.bodies_mut() {
+                    for instr in body.code_mut().elements_mut() {
+                        if let Instruction::CallIndirect(ref mut type_ref, _) = *instr {
+                            *type_ref = map(*type_ref);
+                        }
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+    remove_empty_sections(module);
+    unused.len()
+}
+
+/// Removes type, import, function and code sections which no longer have any entries.
+fn remove_empty_sections(module: &mut Module) {
+    module.sections_mut().retain(|section| match *section {
+        Section::Type(ref types) => !types.types().is_empty(),
+        Section::Import(ref imports) => !imports.entries().is_empty(),
+        Section::Function(ref functions) => !functions.entries().is_empty(),
+        Section::Code(ref code) => !code.bodies().is_empty(),
+        _ => true,
+    });
+}
+
+/// Appends a global defined by the module and returns its index.
+pub fn add_global(module: &mut Module, global: GlobalEntry) -> u32 {
+    if module.global_section().is_none() {
+        insert_section(
+            module,
+            Section::Global(GlobalSection::with_entries(Vec::new())),
+        );
+    }
+    let imported = module.import_count(ImportCountType::Global);
+    let globals = module
+        .global_section_mut()
+        .expect("global section exists")
+        .entries_mut();
+    globals.push(global);
+    (imported + globals.len() - 1) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use parity_wasm::elements::{deserialize_buffer, ValueType};
+    use transform::*;
+
+    #[test]
+    fn add_import_shifts_functions() {
+        // (type (func)) (func $main (call $main)) (export "main" (func $main))
+        // (table 1 anyfunc) (elem (i32.const 0) $main) (start $main)
+        let code: Vec<u8> = vec![
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x04, 0x01, 0x60, 0x00, 0x00,
+            0x03, 0x02, 0x01, 0x00, 0x04, 0x04, 0x01, 0x70, 0x00, 0x01, 0x07, 0x08, 0x01, 0x04,
+            0x6d, 0x61, 0x69, 0x6e, 0x00, 0x00, 0x08, 0x01, 0x00, 0x09, 0x07, 0x01, 0x00, 0x41,
+            0x00, 0x0b, 0x01, 0x00, 0x0a, 0x06, 0x01, 0x04, 0x00, 0x10, 0x00, 0x0b,
+        ];
+        let mut module = deserialize_buffer::<Module>(&code).unwrap();
+        let signature = FunctionType::new(vec![ValueType::I64], None);
+        assert_eq!(
+            add_function_import(&mut module, "ethereum", "useGas", &signature),
+            0
+        );
+
+        let module =
+            deserialize_buffer::<Module>(&serialize(module).unwrap()).expect("valid module");
+        assert_eq!(module.type_section().unwrap().types().len(), 2);
+        assert_eq!(module.import_section().unwrap().functions(), 1);
+        assert_eq!(
+            module.code_section().unwrap().bodies()[0].code().elements()[0],
+            Instruction::Call(1)
+        );
+        assert_eq!(
+            *module.export_section().unwrap().entries()[0].internal(),
+            Internal::Function(1)
+        );
+        assert_eq!(
+            module.elements_section().unwrap().entries()[0].members(),
+            &[1]
+        );
+        assert_eq!(module.start_section(), Some(1));
+    }
+}